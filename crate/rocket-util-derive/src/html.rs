@@ -26,8 +26,13 @@
 // DEALINGS IN THE SOFTWARE.
 
 use {
+    memchr::{
+        memchr,
+        memchr3,
+    },
     proc_macro2::TokenStream,
     quote::{
+        format_ident,
         quote,
         quote_spanned,
     },
@@ -38,23 +43,41 @@ use {
             Parse,
             ParseStream,
         },
+        punctuated::Punctuated,
         spanned::Spanned as _,
+        visit_mut::{
+            self,
+            VisitMut,
+        },
     },
 };
 
+/// Returns the index of the first byte which needs to be escaped in HTML.
+fn next_special(bytes: &[u8]) -> Option<usize> {
+    // special bytes are often close together, e.g. in code samples, where scanning a few bytes directly is faster than calling memchr
+    let (head, tail) = bytes.split_at(bytes.len().min(16));
+    if let Some(idx) = head.iter().position(|b| matches!(b, b'"' | b'&' | b'<' | b'>')) { return Some(idx) }
+    let quot_amp_lt = memchr3(b'"', b'&', b'<', tail);
+    // only search up to the first match of the other three so each byte is scanned at most twice
+    memchr(b'>', &tail[..quot_amp_lt.unwrap_or(tail.len())]).or(quot_amp_lt).map(|idx| head.len() + idx)
+}
+
 fn escape_html(s: &str) -> String {
-    let mut escaped = Vec::with_capacity(s.len());
-    for b in s.bytes() {
-        match b {
-            b'"' => escaped.extend_from_slice(b"&quot;"),
-            b'&' => escaped.extend_from_slice(b"&amp;"),
-            b'<' => escaped.extend_from_slice(b"&lt;"),
-            b'>' => escaped.extend_from_slice(b"&gt;"),
-            _ => escaped.push(b),
-        }
+    let mut escaped = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(idx) = next_special(rest.as_bytes()) {
+        escaped.push_str(&rest[..idx]);
+        escaped.push_str(match rest.as_bytes()[idx] {
+            b'"' => "&quot;",
+            b'&' => "&amp;",
+            b'<' => "&lt;",
+            b'>' => "&gt;",
+            _ => unreachable!(),
+        });
+        rest = &rest[idx + 1..];
     }
-    //SAFETY: `escaped` is derived from a valid UTF-8 string, with only ASCII characters replaced with other ASCII characters. Since UTF-8 is self-synchronizing, `escaped` remains valid UTF-8.
-    unsafe { String::from_utf8_unchecked(escaped) }
+    escaped.push_str(rest);
+    escaped
 }
 
 enum AttrValue {
@@ -92,6 +115,27 @@ impl Parse for Attr {
     }
 }
 
+fn parse_attrs(input: ParseStream<'_>) -> Result<Vec<Attr>> {
+    Ok(if input.peek(token::Paren) {
+        let content;
+        parenthesized!(content in input);
+        content.parse_terminated(Attr::parse, Token![,])?.into_iter().collect()
+    } else {
+        Vec::default()
+    })
+}
+
+/// Parses the remaining segments of a path to a component or layout.
+fn parse_path(first: Ident, input: ParseStream<'_>) -> Result<Path> {
+    let mut segments = Punctuated::new();
+    segments.push(PathSegment::from(first));
+    while input.peek(Token![::]) {
+        let _ = input.parse::<Token![::]>()?;
+        segments.push(PathSegment::from(input.parse::<Ident>()?));
+    }
+    Ok(Path { leading_colon: None, segments })
+}
+
 enum Content {
     Empty,
     Flat(Expr),
@@ -140,6 +184,18 @@ impl Parse for MatchArm {
 }
 
 enum Entry {
+    Children,
+    Flush(proc_macro2::Span),
+    Component {
+        path: Path,
+        attrs: Vec<Attr>,
+        content: Content,
+    },
+    Extends {
+        path: Path,
+        attrs: Vec<Attr>,
+        slots: Vec<(Ident, Input)>,
+    },
     For {
         pat: Pat,
         expr: Expr,
@@ -169,6 +225,10 @@ enum Entry {
         attrs: Vec<Attr>,
         content: Content,
     },
+    Slot {
+        name: Ident,
+        default: Input,
+    },
 }
 
 impl Entry {
@@ -239,6 +299,42 @@ impl Parse for Entry {
             } else if lookahead.peek(Ident) {
                 let ident = input.parse::<Ident>()?;
                 match &*ident.to_string() {
+                    "children" => Self::Children,
+                    "flush" => Self::Flush(ident.span()),
+                    "extends" => {
+                        let path = parse_path(input.parse()?, input)?;
+                        let attrs = parse_attrs(input)?;
+                        let content;
+                        braced!(content in input);
+                        let mut slots = Vec::<(Ident, Input)>::default();
+                        while !content.is_empty() {
+                            let _ = content.parse::<Token![@]>()?;
+                            let keyword = content.parse::<Ident>()?;
+                            if keyword != "slot" {
+                                return Err(Error::new(keyword.span(), "only `@slot` can be used directly inside `@extends`"))
+                            }
+                            let name = content.parse::<Ident>()?;
+                            if slots.iter().any(|(filled, _)| *filled == name) {
+                                return Err(Error::new(name.span(), format!("slot `{name}` is already filled")))
+                            }
+                            let body;
+                            braced!(body in content);
+                            slots.push((name, body.parse()?));
+                        }
+                        Self::Extends { path, attrs, slots }
+                    }
+                    "slot" => {
+                        let name = input.parse()?;
+                        let default = if input.peek(Token![;]) {
+                            let _ = input.parse::<Token![;]>()?;
+                            Input(Vec::default())
+                        } else {
+                            let content;
+                            braced!(content in input);
+                            content.parse()?
+                        };
+                        Self::Slot { name, default }
+                    }
                     "unimplemented" => Self::Unimplemented,
                     "unreachable" => Self::Unreachable,
                     _ => return Err(Error::new(ident.span(), "unexpected keyword")),
@@ -247,15 +343,13 @@ impl Parse for Entry {
                 return Err(lookahead.error())
             }
         } else if lookahead.peek(Ident::peek_any) {
-            let tag = Some(Ident::parse_any(input)?);
-            let attrs = if input.peek(token::Paren) {
-                let content;
-                parenthesized!(content in input);
-                content.parse_terminated(Attr::parse, Token![,])?.into_iter().collect()
+            let ident = Ident::parse_any(input)?;
+            // components are distinguished from tags by being capitalized or given as a path
+            if input.peek(Token![::]) || ident.unraw().to_string().starts_with(|c: char| c.is_ascii_uppercase()) {
+                Self::Component { path: parse_path(ident, input)?, attrs: parse_attrs(input)?, content: input.parse()? }
             } else {
-                Vec::default()
-            };
-            Self::Simple { tag, attrs, content: input.parse()? }
+                Self::Simple { tag: Some(ident), attrs: parse_attrs(input)?, content: input.parse()? }
+            }
         } else if lookahead.peek(Token![;]) || lookahead.peek(Token![:]) || lookahead.peek(token::Brace) {
             Self::Simple { tag: None, attrs: Vec::default(), content: input.parse()? }
         } else {
@@ -264,31 +358,182 @@ impl Parse for Entry {
     }
 }
 
-impl Entry {
-    fn to_tokens(self, internal: bool) -> TokenStream {
-        if let Some(html) = self.to_string() {
-            return quote!(__rocket_util_buf.0.push_str(#html);)
+/// Whether dynamic values of this attribute are rendered using `ToHtmlUrl` rather than `ToHtml`.
+fn is_url_attr(name: &Ident) -> bool {
+    matches!(
+        &*name.unraw().to_string().to_ascii_lowercase(),
+        "action" | "background" | "cite" | "codebase" | "data" | "formaction" | "href" | "icon" | "longdesc" | "manifest" | "poster" | "src"
+    )
+}
+
+/// The kind of item whose body is being generated.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Html,
+    Stream,
+    /// Component children or `@extends` slots inside `html_stream!`. These are rendered in closures, so `@flush` couldn't stop rendering.
+    StreamNested,
+    Component,
+    Layout,
+}
+
+impl Mode {
+    /// The mode for entries which are rendered in a closure, e.g. component children.
+    fn nested(self) -> Self {
+        if self == Self::Stream { Self::StreamNested } else { self }
+    }
+}
+
+fn prop_fields(attrs: Vec<Attr>) -> impl Iterator<Item = TokenStream> {
+    attrs.into_iter().map(|Attr { name, value }| match value {
+        AttrValue::Empty => quote!(#name),
+        AttrValue::Simple(value) => quote!(#name: #value),
+        AttrValue::Optional(value) => quote_spanned!(value.span()=> #name: compile_error!("props can't be optional")),
+    })
+}
+
+/// The kind of text content an entry is rendered into.
+#[derive(Clone, Copy)]
+enum Context {
+    Normal,
+    /// The body of a `<script>` element, which is not HTML-escaped by browsers.
+    Script,
+    /// The body of a `<style>` element, which is not HTML-escaped by browsers.
+    Style,
+}
+
+impl Context {
+    fn for_tag(tag: &Ident) -> Self {
+        match &*tag.unraw().to_string().to_ascii_lowercase() {
+            "script" => Self::Script,
+            "style" => Self::Style,
+            _ => Self::Normal,
         }
+    }
+
+    /// Renders a literal as it should appear in this context.
+    fn literal(self, value: &str, span: proc_macro2::Span) -> Result<String> {
+        match self {
+            Self::Normal => Ok(escape_html(value)),
+            Self::Script => {
+                let lowercase = value.to_ascii_lowercase();
+                if lowercase.contains("</script") || lowercase.contains("<!--") {
+                    Err(Error::new(span, "literals in <script> can't contain `</script` or `<!--`"))
+                } else {
+                    Ok(value.to_owned())
+                }
+            }
+            Self::Style => if value.to_ascii_lowercase().contains("</style") {
+                Err(Error::new(span, "literals in <style> can't contain `</style`"))
+            } else {
+                Ok(value.to_owned())
+            },
+        }
+    }
+
+    fn flat_content_to_string(self, expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Lit(ExprLit { attrs, lit: Lit::Char(c) }) if attrs.is_empty() => self.literal(&c.value().to_string(), c.span()).ok(),
+            Expr::Lit(ExprLit { attrs, lit: Lit::Str(s) }) if attrs.is_empty() => self.literal(&s.value(), s.span()).ok(),
+            _ => None,
+        }
+    }
+
+    fn flat_content_to_tokens(self, expr: Expr, rocket_util: &TokenStream) -> TokenStream {
+        let literal = match expr {
+            Expr::Lit(ExprLit { ref attrs, lit: Lit::Char(ref c) }) if attrs.is_empty() => self.literal(&c.value().to_string(), c.span()),
+            Expr::Lit(ExprLit { ref attrs, lit: Lit::Str(ref s) }) if attrs.is_empty() => self.literal(&s.value(), s.span()),
+            _ => return match self {
+                Self::Normal => quote_spanned!(expr.span()=> #rocket_util::ToHtml::write_html(&(#expr), &mut *__rocket_util_buf);),
+                Self::Script => quote_spanned!(expr.span()=> #rocket_util::ToScript::write_script(&(#expr), &mut *__rocket_util_buf);),
+                Self::Style => quote_spanned!(expr.span()=> #rocket_util::ToStyle::write_style(&(#expr), &mut *__rocket_util_buf);),
+            },
+        };
+        match literal {
+            Ok(literal) => quote!(#rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, #literal);),
+            Err(e) => e.into_compile_error(),
+        }
+    }
+}
+
+impl Entry {
+    fn into_tokens(self, internal: bool, mode: Mode, context: Context) -> TokenStream {
         let rocket_util = if internal { quote!(crate) } else { quote!(::rocket_util) };
+        if let Some(html) = self.to_string(context) {
+            return quote!(#rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, #html);)
+        }
         match self {
+            Self::Children => if mode == Mode::Component {
+                quote!(__rocket_util_children(&mut *__rocket_util_buf);)
+            } else {
+                quote!(compile_error!("`@children` can only be used in the body of a #[component]");)
+            },
+            Self::Flush(span) => if mode == Mode::Stream {
+                quote!(if !#rocket_util::HtmlWriter::flush(&mut *__rocket_util_buf) { return })
+            } else if mode == Mode::StreamNested {
+                quote_spanned!(span=> compile_error!("`@flush` can't be used inside component children or `@extends` slots");)
+            } else {
+                quote_spanned!(span=> compile_error!("`@flush` can only be used in `html_stream!`");)
+            },
+            Self::Component { path, attrs, content } => {
+                if !matches!(context, Context::Normal) {
+                    return quote_spanned!(path.span()=> compile_error!("components can't be used inside <script> or <style>");)
+                }
+                let fields = prop_fields(attrs);
+                let children = match content {
+                    Content::Empty => quote!(),
+                    Content::Flat(expr) => Context::Normal.flat_content_to_tokens(expr, &rocket_util),
+                    Content::Nested(Input(entries)) => {
+                        let body = entries.into_iter().map(|entry| entry.into_tokens(internal, mode.nested(), Context::Normal));
+                        quote!(#(#body)*)
+                    }
+                };
+                quote! {
+                    #rocket_util::Component::render(
+                        #path { #(#fields,)* },
+                        &mut *__rocket_util_buf,
+                        |__rocket_util_buf: &mut dyn #rocket_util::HtmlWriter| { #children },
+                    );
+                }
+            }
+            Self::Extends { path, attrs, slots } => {
+                if !matches!(context, Context::Normal) {
+                    return quote_spanned!(path.span()=> compile_error!("layouts can't be used inside <script> or <style>");)
+                }
+                let fields = prop_fields(attrs);
+                let slots = slots.into_iter().map(|(name, Input(entries))| {
+                    let body = entries.into_iter().map(|entry| entry.into_tokens(internal, mode.nested(), Context::Normal));
+                    quote! {
+                        __rocket_util_extends_slots.#name = ::core::option::Option::Some(::std::boxed::Box::new(
+                            |__rocket_util_buf: &mut dyn #rocket_util::HtmlWriter| { #(#body)* },
+                        ));
+                    }
+                });
+                quote! {{
+                    let __rocket_util_layout = #path { #(#fields,)* };
+                    let mut __rocket_util_extends_slots = #rocket_util::Layout::slots(&__rocket_util_layout);
+                    #(#slots)*
+                    #rocket_util::Layout::render(__rocket_util_layout, &mut *__rocket_util_buf, __rocket_util_extends_slots);
+                }}
+            }
             Self::For { pat, expr, body } => {
-                let body = body.0.into_iter().map(|entry| entry.to_tokens(internal));
+                let body = body.0.into_iter().map(|entry| entry.into_tokens(internal, mode, context));
                 quote!(for #pat in #expr { #(#body)* })
             }
             Self::If { cond, then_branch, else_branch: Some(else_branch) } => {
-                let then_branch = then_branch.0.into_iter().map(|entry| entry.to_tokens(internal));
-                let else_branch = else_branch.to_tokens(internal);
+                let then_branch = then_branch.0.into_iter().map(|entry| entry.into_tokens(internal, mode, context));
+                let else_branch = else_branch.into_tokens(internal, mode, context);
                 quote!(if #cond { #(#then_branch)* } else { #else_branch })
             }
             Self::If { cond, then_branch, else_branch: None } => {
-                let then_branch = then_branch.0.into_iter().map(|entry| entry.to_tokens(internal));
+                let then_branch = then_branch.0.into_iter().map(|entry| entry.into_tokens(internal, mode, context));
                 quote!(if #cond { #(#then_branch)* })
             }
             Self::Let { pat, init } => quote!(let #pat = #init;),
             Self::Match { expr, arms } => {
                 let arms = arms.into_iter().map(|MatchArm { pat, guard, body }| {
                     let guard = guard.map(|guard| quote!(if #guard));
-                    let body = body.to_tokens(internal);
+                    let body = body.into_tokens(internal, mode, context);
                     quote!(#pat #guard => { #body })
                 });
                 quote!(match #expr { #(#arms),* })
@@ -296,10 +541,28 @@ impl Entry {
             Self::Unimplemented => quote!(unimplemented!();), //TODO stop generating code after this
             Self::Unreachable => quote!(unreachable!();), //TODO stop generating code after this
             Self::While { cond, body } => {
-                let body = body.0.into_iter().map(|entry| entry.to_tokens(internal));
+                let body = body.0.into_iter().map(|entry| entry.into_tokens(internal, mode, context));
                 quote!(while #cond { #(#body)* })
             }
+            Self::Slot { name, default } => {
+                if mode != Mode::Layout {
+                    return quote_spanned!(name.span()=> compile_error!("`@slot` can only be used in the body of a #[layout] or directly inside `@extends`");)
+                }
+                if !matches!(context, Context::Normal) {
+                    return quote_spanned!(name.span()=> compile_error!("slots can't be used inside <script> or <style>");)
+                }
+                let default = default.0.into_iter().map(|entry| entry.into_tokens(internal, mode, Context::Normal));
+                quote_spanned! {name.span()=>
+                    match __rocket_util_slots.#name {
+                        ::core::option::Option::Some(__rocket_util_slot) => __rocket_util_slot(&mut *__rocket_util_buf),
+                        ::core::option::Option::None => { #(#default)* }
+                    }
+                }
+            }
             Self::Simple { tag: Some(tag), attrs, content } => {
+                if !matches!(context, Context::Normal) {
+                    return quote_spanned!(tag.span()=> compile_error!("HTML tags can't be nested inside <script> or <style>");)
+                }
                 let is_void = matches!(
                     &*tag.unraw().to_string().to_ascii_lowercase(),
                     "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" | "link" | "meta" | "param" | "source" | "track" | "wbr"
@@ -307,21 +570,12 @@ impl Entry {
                 if is_void && !matches!(content, Content::Empty) {
                     return quote_spanned!(tag.span()=> compile_error!("this HTML tag must be empty");)
                 }
+                let child_context = Context::for_tag(&tag);
                 let content = match content {
                     Content::Empty => quote!(),
-                    Content::Flat(expr) => match expr {
-                        Expr::Lit(ExprLit { attrs, lit: Lit::Char(c) }) if attrs.is_empty() => {
-                            let escaped = escape_html(&c.value().to_string());
-                            quote!(__rocket_util_buf.0.push_str(#escaped);)
-                        }
-                        Expr::Lit(ExprLit { attrs, lit: Lit::Str(s) }) if attrs.is_empty() => {
-                            let escaped = escape_html(&s.value());
-                            quote!(__rocket_util_buf.0.push_str(#escaped);)
-                        }
-                        _ => quote_spanned!(expr.span()=> #rocket_util::ToHtml::push_html(&(#expr), &mut __rocket_util_buf);),
-                    },
+                    Content::Flat(expr) => child_context.flat_content_to_tokens(expr, &rocket_util),
                     Content::Nested(Input(entries)) => {
-                        let body = entries.into_iter().map(|entry| entry.to_tokens(internal));
+                        let body = entries.into_iter().map(|entry| entry.into_tokens(internal, mode, child_context));
                         quote! {{ #(#body)* }}
                     }
                 };
@@ -329,37 +583,47 @@ impl Entry {
                 let attrs = attrs.into_iter().map(|Attr { name, value }| match value {
                     AttrValue::Empty => {
                         let attr = format!(" {}", name.unraw().to_string().replace('_', "-"));
-                        quote!(__rocket_util_buf.0.push_str(#attr);)
+                        quote!(#rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, #attr);)
                     }
                     AttrValue::Simple(value) => match value {
                         Expr::Lit(ExprLit { attrs, lit: Lit::Char(c) }) if attrs.is_empty() => {
                             let attr = format!(" {}=\"{}\"", name.unraw().to_string().replace('_', "-"), escape_html(&c.value().to_string()));
-                            quote!(__rocket_util_buf.0.push_str(#attr);)
+                            quote!(#rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, #attr);)
                         }
                         Expr::Lit(ExprLit { attrs, lit: Lit::Str(s) }) if attrs.is_empty() => {
                             let attr = format!(" {}=\"{}\"", name.unraw().to_string().replace('_', "-"), escape_html(&s.value()));
-                            quote!(__rocket_util_buf.0.push_str(#attr);)
+                            quote!(#rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, #attr);)
                         }
                         _ => {
                             let attr = format!(" {}=\"", name.unraw().to_string().replace('_', "-"));
+                            let push_value = if is_url_attr(&name) {
+                                quote_spanned!(value.span()=> #rocket_util::ToHtmlUrl::write_html_url(&(#value), &mut *__rocket_util_buf);)
+                            } else {
+                                quote_spanned!(value.span()=> #rocket_util::ToHtml::write_html(&(#value), &mut *__rocket_util_buf);)
+                            };
                             quote_spanned! {value.span()=>
-                                __rocket_util_buf.0.push_str(#attr);
-                                #rocket_util::ToHtml::push_html(&(#value), &mut __rocket_util_buf);
-                                __rocket_util_buf.0.push('"');
+                                #rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, #attr);
+                                #push_value
+                                #rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, "\"");
                             }
                         }
                     },
                     AttrValue::Optional(value) => {
                         let attr_no_value = format!(" {}", name.unraw().to_string().replace('_', "-"));
                         let attr_with_value = format!(" {}=\"", name.unraw().to_string().replace('_', "-"));
+                        let push_value = if is_url_attr(&name) {
+                            quote_spanned!(value.span()=> #rocket_util::ToHtmlUrl::write_html_url(&__rocket_util_value, &mut *__rocket_util_buf);)
+                        } else {
+                            quote_spanned!(value.span()=> #rocket_util::ToHtml::write_html(&__rocket_util_value, &mut *__rocket_util_buf);)
+                        };
                         quote_spanned! {value.span()=>
                             match #rocket_util::OptionalAttr::attr_value(#value) {
                                 ::core::option::Option::None => {}
-                                ::core::option::Option::Some(::core::option::Option::None) => __rocket_util_buf.0.push_str(#attr_no_value),
+                                ::core::option::Option::Some(::core::option::Option::None) => #rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, #attr_no_value),
                                 ::core::option::Option::Some(::core::option::Option::Some(__rocket_util_value)) => {
-                                    __rocket_util_buf.0.push_str(#attr_with_value);
-                                    #rocket_util::ToHtml::push_html(&__rocket_util_value, &mut __rocket_util_buf);
-                                    __rocket_util_buf.0.push('"');
+                                    #rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, #attr_with_value);
+                                    #push_value
+                                    #rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, "\"");
                                 }
                             }
                         }
@@ -367,12 +631,12 @@ impl Entry {
                 });
                 let close_tag = (!is_void).then(|| {
                     let close_tag = format!("</{}>", tag.unraw());
-                    quote!(__rocket_util_buf.0.push_str(#close_tag);)
+                    quote!(#rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, #close_tag);)
                 });
                 quote! {
-                    __rocket_util_buf.0.push_str(#open_tag);
+                    #rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, #open_tag);
                     #(#attrs)*
-                    __rocket_util_buf.0.push('>');
+                    #rocket_util::HtmlWriter::write_raw(&mut *__rocket_util_buf, ">");
                     #content
                     #close_tag
                 }
@@ -381,19 +645,9 @@ impl Entry {
                 assert!(attrs.is_empty());
                 match content {
                     Content::Empty => quote!(),
-                    Content::Flat(expr) => match expr {
-                        Expr::Lit(ExprLit { attrs, lit: Lit::Char(c) }) if attrs.is_empty() => {
-                            let escaped = escape_html(&c.value().to_string());
-                            quote!(__rocket_util_buf.0.push_str(#escaped);)
-                        }
-                        Expr::Lit(ExprLit { attrs, lit: Lit::Str(s) }) if attrs.is_empty() => {
-                            let escaped = escape_html(&s.value());
-                            quote!(__rocket_util_buf.0.push_str(#escaped);)
-                        }
-                        _ => quote_spanned!(expr.span()=> #rocket_util::ToHtml::push_html(&(#expr), &mut __rocket_util_buf);),
-                    },
+                    Content::Flat(expr) => context.flat_content_to_tokens(expr, &rocket_util),
                     Content::Nested(Input(entries)) => {
-                        let body = entries.into_iter().map(|entry| entry.to_tokens(internal));
+                        let body = entries.into_iter().map(|entry| entry.into_tokens(internal, mode, context));
                         quote! {{ #(#body)* }}
                     }
                 }
@@ -401,15 +655,17 @@ impl Entry {
         }
     }
 
-    fn to_string(&self) -> Option<String> {
+    fn to_string(&self, context: Context) -> Option<String> {
         match self {
-            Self::For { .. } | Self::If { .. } | Self::Let { .. } | Self::Match { .. } | Self::Unimplemented | Self::Unreachable | Self::While { .. } => None,
+            Self::Children | Self::Flush(_) | Self::Component { .. } | Self::Extends { .. } | Self::Slot { .. } | Self::For { .. } | Self::If { .. } | Self::Let { .. } | Self::Match { .. } | Self::Unimplemented | Self::Unreachable | Self::While { .. } => None,
             Self::Simple { tag: Some(tag), attrs, content } => {
+                if !matches!(context, Context::Normal) { return None }
                 let is_void = matches!(
                     &*tag.unraw().to_string().to_ascii_lowercase(),
                     "area" | "base" | "br" | "col" | "embed" | "hr" | "img" | "input" | "link" | "meta" | "param" | "source" | "track" | "wbr"
                 );
                 if is_void && !matches!(content, Content::Empty) { return None }
+                let child_context = Context::for_tag(tag);
                 let mut buf = format!("<{}", tag.unraw());
                 for Attr { name, value } in attrs {
                     buf.push_str(&match value {
@@ -427,13 +683,9 @@ impl Entry {
                 buf.push('>');
                 match content {
                     Content::Empty => {}
-                    Content::Flat(expr) => match expr {
-                        Expr::Lit(ExprLit { attrs, lit: Lit::Char(c) }) if attrs.is_empty() => buf.push_str(&escape_html(&c.value().to_string())),
-                        Expr::Lit(ExprLit { attrs, lit: Lit::Str(s) }) if attrs.is_empty() => buf.push_str(&escape_html(&s.value())),
-                        _ => return None,
-                    },
+                    Content::Flat(expr) => buf.push_str(&child_context.flat_content_to_string(expr)?),
                     Content::Nested(Input(entries)) => for entry in entries {
-                        buf.push_str(&entry.to_string()?);
+                        buf.push_str(&entry.to_string(child_context)?);
                     },
                 }
                 if !is_void {
@@ -445,15 +697,11 @@ impl Entry {
                 assert!(attrs.is_empty());
                 Some(match content {
                     Content::Empty => String::default(),
-                    Content::Flat(expr) => match expr {
-                        Expr::Lit(ExprLit { attrs, lit: Lit::Char(c) }) if attrs.is_empty() => escape_html(&c.value().to_string()),
-                        Expr::Lit(ExprLit { attrs, lit: Lit::Str(s) }) if attrs.is_empty() => escape_html(&s.value()),
-                        _ => return None,
-                    },
+                    Content::Flat(expr) => context.flat_content_to_string(expr)?,
                     Content::Nested(Input(entries)) => {
                         let mut buf = String::default();
                         for entry in entries {
-                            buf.push_str(&entry.to_string()?);
+                            buf.push_str(&entry.to_string(context)?);
                         }
                         buf
                     }
@@ -463,6 +711,37 @@ impl Entry {
     }
 }
 
+impl Entry {
+    /// Collects the names of the slots declared in the body of a layout.
+    fn collect_slots(&self, slots: &mut Vec<Ident>) {
+        match self {
+            Self::Children | Self::Flush(_) | Self::Let { .. } | Self::Unimplemented | Self::Unreachable => {}
+            Self::Component { content, .. } | Self::Simple { content, .. } => if let Content::Nested(body) = content {
+                body.collect_slots(slots);
+            },
+            Self::Extends { slots: filled, .. } => for (_, body) in filled {
+                body.collect_slots(slots);
+            },
+            Self::For { body, .. } | Self::While { body, .. } => body.collect_slots(slots),
+            Self::If { then_branch, else_branch, .. } => {
+                then_branch.collect_slots(slots);
+                if let Some(else_branch) = else_branch {
+                    else_branch.collect_slots(slots);
+                }
+            }
+            Self::Match { arms, .. } => for arm in arms {
+                arm.body.collect_slots(slots);
+            },
+            Self::Slot { name, default } => {
+                if !slots.contains(name) {
+                    slots.push(name.clone());
+                }
+                default.collect_slots(slots);
+            }
+        }
+    }
+}
+
 struct Input(Vec<Entry>);
 
 impl Parse for Input {
@@ -476,18 +755,264 @@ impl Parse for Input {
 }
 
 impl Input {
-    fn to_tokens(self, internal: bool) -> TokenStream {
+    fn collect_slots(&self, slots: &mut Vec<Ident>) {
+        for entry in &self.0 {
+            entry.collect_slots(slots);
+        }
+    }
+
+    fn into_tokens(self, internal: bool) -> TokenStream {
         let rocket_util = if internal { quote!(crate) } else { quote!(::rocket_util) };
-        let entries = self.0.into_iter().map(|entry| entry.to_tokens(internal));
+        let entries = self.0.into_iter().map(|entry| entry.into_tokens(internal, Mode::Html, Context::Normal));
         quote! {{
-            let mut __rocket_util_buf = #rocket_util::rocket::response::content::RawHtml(::std::string::String::new());
-            #(#entries)*
-            __rocket_util_buf
+            let mut __rocket_util_html = #rocket_util::rocket::response::content::RawHtml(::std::string::String::new());
+            {
+                let __rocket_util_buf: &mut dyn #rocket_util::HtmlWriter = &mut __rocket_util_html;
+                #(#entries)*
+            }
+            __rocket_util_html
         }}
     }
 }
 
 pub(crate) fn mac(input: proc_macro::TokenStream, internal: bool) -> proc_macro::TokenStream {
-    let tokens = parse_macro_input!(input as Input).to_tokens(internal);
+    let tokens = parse_macro_input!(input as Input).into_tokens(internal);
     proc_macro::TokenStream::from(quote! {{ #[allow(unused)] #tokens }})
 }
+
+struct WriteInput {
+    writer: Expr,
+    input: Input,
+}
+
+impl Parse for WriteInput {
+    fn parse(input: ParseStream<'_>) -> Result<Self> {
+        let writer = input.parse()?;
+        input.parse::<Token![,]>()?;
+        Ok(Self { writer, input: input.parse()? })
+    }
+}
+
+pub(crate) fn mac_write(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let WriteInput { writer, input: Input(entries) } = parse_macro_input!(input as WriteInput);
+    let entries = entries.into_iter().map(|entry| entry.into_tokens(false, Mode::Html, Context::Normal));
+    proc_macro::TokenStream::from(quote! {{
+        #[allow(unused)] {
+            let __rocket_util_buf: &mut dyn ::rocket_util::HtmlWriter = &mut #writer;
+            #(#entries)*
+        }
+    }})
+}
+
+pub(crate) fn mac_stream(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let Input(entries) = parse_macro_input!(input as Input);
+    let entries = entries.into_iter().map(|entry| {
+        let entry = entry.into_tokens(false, Mode::Stream, Context::Normal);
+        quote! {
+            #entry
+            if !::rocket_util::HtmlWriter::flush(&mut *__rocket_util_buf) { return }
+        }
+    });
+    proc_macro::TokenStream::from(quote! {{
+        #[allow(unused)] {
+            ::rocket_util::HtmlStream::new(move |__rocket_util_buf: &mut dyn ::rocket_util::HtmlWriter| {
+                #(#entries)*
+            })
+        }
+    }})
+}
+
+/// Names elided lifetimes in the type of a component prop, since they become fields of the props struct.
+struct NameElidedLifetimes(bool);
+
+impl VisitMut for NameElidedLifetimes {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        if lifetime.ident == "_" {
+            *lifetime = parse_quote!('__rocket_util_props);
+            self.0 = true;
+        }
+    }
+
+    fn visit_type_reference_mut(&mut self, ty: &mut TypeReference) {
+        if ty.lifetime.is_none() {
+            ty.lifetime = Some(parse_quote!('__rocket_util_props));
+            self.0 = true;
+        }
+        visit_mut::visit_type_reference_mut(self, ty);
+    }
+
+    // elided lifetimes in these refer to higher-ranked lifetimes instead
+    fn visit_parenthesized_generic_arguments_mut(&mut self, _: &mut ParenthesizedGenericArguments) {}
+    fn visit_type_bare_fn_mut(&mut self, _: &mut TypeBareFn) {}
+}
+
+/// Shared implementation of the `#[component]` and `#[layout]` attributes.
+fn fragment(args: proc_macro::TokenStream, item: proc_macro::TokenStream, mode: Mode) -> proc_macro::TokenStream {
+    let (attr_name, plural) = match mode {
+        Mode::Html | Mode::Stream | Mode::StreamNested => unreachable!(),
+        Mode::Component => ("component", "components"),
+        Mode::Layout => ("layout", "layouts"),
+    };
+    if !args.is_empty() {
+        let message = format!("#[{attr_name}] does not take arguments");
+        return quote!(compile_error!(#message);).into()
+    }
+    let ItemFn { attrs, vis, sig, block } = parse_macro_input!(item as ItemFn);
+    if let Some(asyncness) = sig.asyncness {
+        let message = format!("{plural} can't be async");
+        return quote_spanned!(asyncness.span()=> compile_error!(#message);).into()
+    }
+    if let ReturnType::Type(_, ty) = sig.output {
+        let message = format!("{plural} don't have a return type");
+        return quote_spanned!(ty.span()=> compile_error!(#message);).into()
+    }
+    let Signature { ident, mut generics, inputs, .. } = sig;
+    let mut name_elided_lifetimes = NameElidedLifetimes(false);
+    let mut fields = Vec::with_capacity(inputs.len());
+    let mut bindings = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let FnArg::Typed(PatType { attrs, pat, mut ty, .. }) = input else {
+            let message = format!("{plural} can't take `self`");
+            return quote_spanned!(input.span()=> compile_error!(#message);).into()
+        };
+        let Pat::Ident(PatIdent { by_ref: None, mutability, ident, subpat: None, .. }) = *pat else {
+            return quote_spanned!(pat.span()=> compile_error!("props must be simple identifiers");).into()
+        };
+        name_elided_lifetimes.visit_type_mut(&mut ty);
+        fields.push(quote!(#(#attrs)* #vis #ident: #ty));
+        bindings.push(quote!(#mutability #ident));
+    }
+    if name_elided_lifetimes.0 {
+        generics.params.insert(0, parse_quote!('__rocket_util_props));
+    }
+    let mut stmts = block.stmts;
+    let mac = match stmts.pop() {
+        Some(Stmt::Macro(StmtMacro { mac, .. }) | Stmt::Expr(Expr::Macro(ExprMacro { mac, .. }), None)) if mac.path.segments.last().is_some_and(|segment| segment.ident == "html") => mac,
+        _ => {
+            let message = format!("the body of a {attr_name} must end with an `html!` invocation");
+            return quote_spanned!(block.brace_token.span.close()=> compile_error!(#message);).into()
+        }
+    };
+    let mac_path = &mac.path;
+    let input = match mac.parse_body::<Input>() {
+        Ok(input) => input,
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let mut slots = Vec::default();
+    input.collect_slots(&mut slots);
+    let body = input.0.into_iter().map(|entry| entry.into_tokens(false, mode, Context::Normal));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let render_body = quote! {
+        // the `html!` invocation is expanded by this attribute, so mark its import as used
+        use #mac_path as _;
+        let Self { #(#bindings,)* } = self;
+        #(#stmts)*
+        #(#body)*
+    };
+    let imp = match mode {
+        Mode::Html | Mode::Stream | Mode::StreamNested => unreachable!(),
+        Mode::Component => quote! {
+            impl #impl_generics ::rocket_util::Component for #ident #ty_generics #where_clause {
+                #[allow(unused)]
+                fn render(
+                    self,
+                    __rocket_util_buf: &mut dyn ::rocket_util::HtmlWriter,
+                    __rocket_util_children: impl ::core::ops::FnOnce(&mut dyn ::rocket_util::HtmlWriter),
+                ) {
+                    #render_body
+                }
+            }
+        },
+        Mode::Layout => {
+            let slots_ident = format_ident!("{}Slots", ident);
+            let slots_doc = format!("The slots of the [`{ident}`] layout.");
+            quote! {
+                #[doc = #slots_doc]
+                #vis struct #slots_ident<'__rocket_util_slots> {
+                    #(#vis #slots: ::rocket_util::Slot<'__rocket_util_slots>,)*
+                    __rocket_util_lifetime: ::core::marker::PhantomData<&'__rocket_util_slots ()>,
+                }
+
+                impl ::core::default::Default for #slots_ident<'_> {
+                    fn default() -> Self {
+                        Self {
+                            #(#slots: ::core::option::Option::None,)*
+                            __rocket_util_lifetime: ::core::marker::PhantomData,
+                        }
+                    }
+                }
+
+                impl #impl_generics ::rocket_util::Layout for #ident #ty_generics #where_clause {
+                    type Slots<'__rocket_util_slots> = #slots_ident<'__rocket_util_slots>;
+
+                    #[allow(unused)]
+                    fn render(
+                        self,
+                        __rocket_util_buf: &mut dyn ::rocket_util::HtmlWriter,
+                        mut __rocket_util_slots: Self::Slots<'_>,
+                    ) {
+                        #render_body
+                    }
+                }
+            }
+        }
+    };
+    proc_macro::TokenStream::from(quote! {
+        #(#attrs)*
+        #vis struct #ident #generics #where_clause {
+            #(#fields,)*
+        }
+
+        #imp
+    })
+}
+
+pub(crate) fn component(args: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    fragment(args, item, Mode::Component)
+}
+
+pub(crate) fn layout(args: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    fragment(args, item, Mode::Layout)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    /// The byte-by-byte escaping which was used before escaping in runs found using `memchr`.
+    fn reference_escape_html(s: &str) -> String {
+        let mut escaped = Vec::with_capacity(s.len());
+        for b in s.bytes() {
+            match b {
+                b'"' => escaped.extend_from_slice(b"&quot;"),
+                b'&' => escaped.extend_from_slice(b"&amp;"),
+                b'<' => escaped.extend_from_slice(b"&lt;"),
+                b'>' => escaped.extend_from_slice(b"&gt;"),
+                _ => escaped.push(b),
+            }
+        }
+        String::from_utf8(escaped).expect("escaping only replaces ASCII bytes")
+    }
+
+    proptest! {
+        #[test]
+        fn escape_html_matches_reference(text in ".*") {
+            prop_assert_eq!(super::escape_html(&text), reference_escape_html(&text));
+        }
+
+        #[test]
+        fn escape_html_matches_reference_around_special_bytes(chars in prop::collection::vec(prop_oneof![
+            Just('"'),
+            Just('&'),
+            Just('<'),
+            Just('>'),
+            Just('é'),
+            Just('€'),
+            Just('😀'),
+            any::<char>(),
+        ], 0..64)) {
+            let text = String::from_iter(chars);
+            prop_assert_eq!(super::escape_html(&text), reference_escape_html(&text));
+        }
+    }
+}
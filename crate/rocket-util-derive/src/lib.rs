@@ -2,8 +2,15 @@
 
 use {
     proc_macro::TokenStream,
-    quote::quote,
-    syn::*,
+    quote::{
+        quote,
+        quote_spanned,
+    },
+    syn::{
+        *,
+        ext::IdentExt as _,
+        spanned::Spanned as _,
+    },
 };
 
 mod html;
@@ -15,37 +22,271 @@ mod html;
 /// * This macro supports `@match`. Don't use commas to separate the match arms.
 /// * This macro also supports `@unimplemented` and `@unreachable` to work around type inference issues with using `: unimplemented!();` or `: unreachable!();`.
 /// * HTML attributes with hyphens should be written with underscores instead, e.g. write `data_foo` instead of `data-foo`.
+/// * Capitalized names or paths, e.g. `Card(title = "x") { ... }`, invoke components defined using [`macro@component`] rather than tags.
+/// * `@extends Name(...) { @slot name { ... } ... }` renders a layout defined using [`macro@layout`].
+/// * Dynamic values of attributes containing URLs, like `href` or `src`, must implement `ToHtmlUrl` rather than `ToHtml`. This rejects plain strings, which can be wrapped in `SafeUrl` to check their scheme.
 #[proc_macro]
 pub fn html(input: TokenStream) -> TokenStream {
     html::mac(input, false)
 }
 
+/// Like [`html!`], but expands to an `HtmlStream`, a responder which sends the HTML to the client while it's being rendered. This reduces memory usage and time to first byte for large pages.
+///
+/// Rendered HTML is sent after each top-level entry and at each `@flush`, e.g. after each row of a large table. Rendering happens on a blocking thread, so like with `async move` blocks, variables used in the macro are moved into it and must be `Send + 'static`. Rendering pauses while the client is not keeping up and stops at the next flush if it disconnects. `@flush` can't be used inside component children or `@extends` slots, since these are rendered in closures which it couldn't return from. If rendering panics, the error is logged and the response body ends with an error, so the client doesn't mistake the truncated page for a complete one.
+#[proc_macro]
+pub fn html_stream(input: TokenStream) -> TokenStream {
+    html::mac_stream(input)
+}
+
+/// Like [`html!`], but writes the HTML to the given `HtmlWriter` instead of returning it, e.g. `write_html!(writer, p : "Hello";)`.
+///
+/// The writer expression is mutably borrowed, so it can be a value like a `String` or `IoWriter`, or a mutable reference. Unlike [`html!`], this allows rendering directly into an existing buffer, a file, or a hasher without building an intermediate string.
+#[proc_macro]
+pub fn write_html(input: TokenStream) -> TokenStream {
+    html::mac_write(input)
+}
+
 #[doc(hidden)]
 #[proc_macro]
 pub fn html_internal(input: TokenStream) -> TokenStream {
     html::mac(input, true)
 }
 
-#[proc_macro_derive(CsrfForm)]
+/// Defines a component which can be used inside the [`html!`] macro.
+///
+/// The function's parameters become the props of the component, and its body must end with an `html!` invocation, which can use `@children` to render the children passed to the component. The function is replaced with a struct of the same name with the props as fields, which implements `rocket_util::Component`. Use it as `Name(prop = value, ...) { children }` inside `html!`, with each prop given exactly once.
+#[proc_macro_attribute]
+pub fn component(args: TokenStream, item: TokenStream) -> TokenStream {
+    html::component(args, item)
+}
+
+/// Defines a layout which can be used inside the [`html!`] macro, e.g. for the skeleton shared by all pages of a site.
+///
+/// Like [`macro@component`], the function's parameters become the props of the layout and its body must end with an `html!` invocation. Instead of `@children`, that invocation declares named slots using `@slot name { default contents }` or `@slot name;` (empty by default). Use it as `@extends Name(prop = value, ...) { @slot name { contents } ... }` inside `html!`, where slots which aren't filled render their default contents. Besides the struct for the props, which implements `rocket_util::Layout`, this generates a struct for the slots, named after the layout with a `Slots` suffix.
+#[proc_macro_attribute]
+pub fn layout(args: TokenStream, item: TokenStream) -> TokenStream {
+    html::layout(args, item)
+}
+
+/// Returns the name under which a field is submitted in a form, taking `#[field(name = "...")]` into account.
+fn form_field_name(field: &Field) -> Result<Option<String>> {
+    let Some(ident) = &field.ident else { return Ok(None) };
+    let mut name = ident.unraw().to_string();
+    for attr in &field.attrs {
+        if attr.path().is_ident("field") {
+            attr.parse_nested_meta(|meta| {
+                let value = meta.value()?.parse::<Expr>()?;
+                if meta.path.is_ident("name") {
+                    let Expr::Lit(ExprLit { lit: Lit::Str(lit), .. }) = value else {
+                        return Err(Error::new_spanned(value, "the name of a CSRF token field must be a string literal"))
+                    };
+                    name = lit.value();
+                }
+                Ok(())
+            })?;
+        }
+    }
+    Ok(Some(name))
+}
+
+/// Implements `rocket_util::CsrfForm` for a struct.
+///
+/// The CSRF token is read from the field marked with `#[csrf]`, or from the field named `csrf` if no field is marked. The field's type must implement `AsRef<str>`, e.g. `String`, `&str`, or `Cow<'_, str>`. If the field is renamed using `#[field(name = "...")]`, the name must be a string literal. Use `CsrfToken::input::<Form>()` to render the token under the field's name.
+///
+/// The error message can be customized using `#[csrf(error_message = path::to::function)]` on the struct, where the function has the same signature as `CsrfForm::csrf_error_message`.
+#[proc_macro_derive(CsrfForm, attributes(csrf))]
 pub fn derive_csrf_form(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let ty = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut error_message = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("csrf")) {
+        if let Err(e) = attr.parse_nested_meta(|meta| if meta.path.is_ident("error_message") {
+            error_message = Some(meta.value()?.parse::<Path>()?);
+            Ok(())
+        } else {
+            Err(meta.error("unknown CSRF form attribute"))
+        }) {
+            return e.into_compile_error().into()
+        }
+    }
+    let error_message = error_message.map(|path| quote! {
+        fn csrf_error_message(accept_language: ::core::option::Option<&str>) -> ::std::borrow::Cow<'static, str> { #path(accept_language) }
+    });
+    let Data::Struct(DataStruct { fields, .. }) = input.data else {
+        return Error::new(ty.span(), "CsrfForm can only be derived for structs").into_compile_error().into()
+    };
+    let mut marked = fields.iter().filter(|field| field.attrs.iter().any(|attr| attr.path().is_ident("csrf")));
+    let field = match (marked.next(), marked.next()) {
+        (Some(field), None) => field,
+        (Some(_), Some(second)) => return Error::new_spanned(second, "multiple fields marked with #[csrf]").into_compile_error().into(),
+        (None, _) => match fields.iter().find(|field| field.ident.as_ref().is_some_and(|ident| ident == "csrf")) {
+            Some(field) => field,
+            None => return Error::new(ty.span(), "missing CSRF token field: add a field named `csrf` or mark a field with #[csrf]").into_compile_error().into(),
+        },
+    };
+    let name = match form_field_name(field) {
+        Ok(Some(name)) => name,
+        Ok(None) => return Error::new_spanned(field, "the CSRF token field must be a named field").into_compile_error().into(),
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let ident = &field.ident;
+    let csrf = quote_spanned!(field.ty.span()=> ::std::convert::AsRef::<str>::as_ref(&self.#ident));
     TokenStream::from(quote! {
-        impl ::rocket_util::CsrfForm for #ty {
-            fn csrf(&self) -> &::std::string::String { &self.csrf }
+        impl #impl_generics ::rocket_util::CsrfForm for #ty #ty_generics #where_clause {
+            fn csrf(&self) -> &str { #csrf }
+
+            fn csrf_field_name() -> &'static str { #name }
+
+            #error_message
         }
     })
 }
 
-#[proc_macro_derive(Error)]
+fn status_attr(attrs: &[Attribute]) -> Result<Option<u16>> {
+    let mut status = None;
+    for attr in attrs {
+        if attr.path().is_ident("status") {
+            if status.is_some() {
+                return Err(Error::new_spanned(attr, "duplicate #[status] attribute"))
+            }
+            let code = attr.parse_args::<LitInt>()?;
+            let value = code.base10_parse::<u16>()?;
+            if !(100..600).contains(&value) {
+                return Err(Error::new(code.span(), "HTTP status codes must be in the range 100..=599"))
+            }
+            status = Some(value);
+        }
+    }
+    Ok(status)
+}
+
+/// Implements [`rocket::response::Responder`](https://docs.rs/rocket/0.5/rocket/response/trait.Responder.html) for an error type.
+///
+/// By default, the response is an internal server error and the error is logged. The status can be overridden using a `#[status(...)]` attribute on the type, or on individual enum variants. Errors are only logged if the status is a server error (5xx).
+#[proc_macro_derive(Error, attributes(status))]
 pub fn derive_error(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let ty = input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let mut generics = input.generics.clone();
+    generics.params.insert(0, parse_quote!('__r));
+    generics.make_where_clause().predicates.push(parse_quote!(Self: ::std::error::Error));
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let default_status = match status_attr(&input.attrs) {
+        Ok(status) => status.unwrap_or(500),
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let status = match input.data {
+        Data::Enum(DataEnum { variants, .. }) => {
+            let mut arms = Vec::with_capacity(variants.len());
+            let mut any_override = false;
+            for variant in variants {
+                let status = match status_attr(&variant.attrs) {
+                    Ok(Some(status)) => { any_override = true; status }
+                    Ok(None) => default_status,
+                    Err(e) => return e.into_compile_error().into(),
+                };
+                let name = variant.ident;
+                arms.push(quote!(Self::#name { .. } => #status));
+            }
+            if any_override {
+                quote!(match &self { #(#arms,)* })
+            } else {
+                quote!(#default_status)
+            }
+        }
+        Data::Struct(DataStruct { fields, .. }) => {
+            for field in fields {
+                if let Some(attr) = field.attrs.iter().find(|attr| attr.path().is_ident("status")) {
+                    return Error::new_spanned(attr, "#[status] is only supported on the type and on enum variants").into_compile_error().into()
+                }
+            }
+            quote!(#default_status)
+        }
+        Data::Union(DataUnion { union_token, .. }) => return Error::new(union_token.span, "unions are not supported").into_compile_error().into(),
+    };
     TokenStream::from(quote! {
-        impl<'r> ::rocket_util::rocket::response::Responder<'r, 'static> for #ty {
-            fn respond_to(self, request: &'r ::rocket_util::rocket::Request<'_>) -> ::rocket_util::rocket::response::Result<'static> {
-                ::rocket_util::rocket::response::Responder::respond_to(::rocket_util::Error(self), request)
+        impl #impl_generics ::rocket_util::rocket::response::Responder<'__r, 'static> for #ty #ty_generics #where_clause {
+            fn respond_to(self, request: &'__r ::rocket_util::rocket::Request<'_>) -> ::rocket_util::rocket::response::Result<'static> {
+                let status = ::rocket_util::rocket::http::Status::new(#status);
+                ::rocket_util::Error(self).respond_with_status(status, request)
             }
         }
     })
 }
+
+/// Implements `rocket_util::FromSuffix` and `UriDisplay<Path>` for an enum with unit variants, for use with `rocket_util::TypedSuffix`.
+///
+/// Each variant matches its name in lowercase by default. This can be overridden using `#[suffix("ext")]`, and additional suffixes can be listed as `#[suffix("jpg", "jpeg")]`. The first suffix is used when displaying the value.
+///
+/// Suffixes may consist of multiple parts, e.g. `#[suffix("tar.gz")]`. When parsing, the longest matching suffix wins, so `archive.tar.gz` is split into `archive` and `tar.gz` even if `gz` is also a known suffix.
+#[proc_macro_derive(FromSuffix, attributes(suffix))]
+pub fn derive_from_suffix(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let ty = input.ident;
+    if !input.generics.params.is_empty() {
+        return Error::new_spanned(input.generics, "FromSuffix can't be derived for generic types").into_compile_error().into()
+    }
+    let Data::Enum(DataEnum { variants, .. }) = input.data else {
+        return Error::new(ty.span(), "FromSuffix can only be derived for enums").into_compile_error().into()
+    };
+    let mut parse_arms = Vec::default();
+    let mut split_suffixes = Vec::default();
+    let mut display_arms = Vec::default();
+    for variant in variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Error::new_spanned(variant.fields, "FromSuffix variants can't have fields").into_compile_error().into()
+        }
+        let mut suffixes = Vec::default();
+        for attr in variant.attrs.iter().filter(|attr| attr.path().is_ident("suffix")) {
+            match attr.parse_args_with(punctuated::Punctuated::<LitStr, Token![,]>::parse_terminated) {
+                Ok(lits) => suffixes.extend(lits),
+                Err(e) => return e.into_compile_error().into(),
+            }
+        }
+        if suffixes.is_empty() {
+            suffixes.push(LitStr::new(&variant.ident.unraw().to_string().to_lowercase(), variant.ident.span()));
+        }
+        if let Some(lit) = suffixes.iter().find(|lit| lit.value().is_empty() || lit.value().starts_with('.') || lit.value().ends_with('.')) {
+            return Error::new(lit.span(), "suffixes must be nonempty and can't start or end with `.`").into_compile_error().into()
+        }
+        let name = variant.ident;
+        split_suffixes.extend(suffixes.iter().map(|suffix| (suffix.clone(), name.clone())));
+        let canonical = &suffixes[0];
+        parse_arms.push(quote!(#(#suffixes)|* => ::core::option::Option::Some(Self::#name)));
+        display_arms.push(quote!(Self::#name => #canonical));
+    }
+    // longest suffix first
+    split_suffixes.sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.value().len()));
+    let split_arms = split_suffixes.into_iter().map(|(suffix, name)| quote! {
+        if let ::core::option::Option::Some(prefix) = param.strip_suffix(#suffix).and_then(|prefix| prefix.strip_suffix('.')) {
+            return ::core::option::Option::Some((prefix, Self::#name))
+        }
+    });
+    TokenStream::from(quote! {
+        impl<'a> ::rocket_util::FromSuffix<'a> for #ty {
+            fn from_suffix(suffix: &'a str) -> ::core::option::Option<Self> {
+                match suffix {
+                    #(#parse_arms,)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+
+            fn split_suffix(param: &'a str) -> ::core::option::Option<(&'a str, Self)> {
+                #(#split_arms)*
+                ::core::option::Option::None
+            }
+        }
+
+        impl ::rocket_util::rocket::http::uri::fmt::UriDisplay<::rocket_util::rocket::http::uri::fmt::Path> for #ty {
+            fn fmt(&self, f: &mut ::rocket_util::rocket::http::uri::fmt::Formatter<'_, ::rocket_util::rocket::http::uri::fmt::Path>) -> ::std::fmt::Result {
+                ::rocket_util::rocket::http::uri::fmt::UriDisplay::fmt(match self {
+                    #(#display_arms,)*
+                }, f)
+            }
+        }
+
+        ::rocket_util::rocket::http::impl_from_uri_param_identity!([::rocket_util::rocket::http::uri::fmt::Path] #ty);
+    })
+}
@@ -0,0 +1,32 @@
+use {
+    criterion::{
+        BenchmarkId,
+        Criterion,
+        Throughput,
+        criterion_group,
+        criterion_main,
+    },
+    rocket_util::ToHtml as _,
+};
+
+#[path = "../tests/reference/mod.rs"] mod reference;
+
+const ENGLISH: &str = "The \"Rocket & Friends\" meetup starts at 7 pm. Bring your laptop if you'd like to follow along; we'll cover request guards, fairings, and templating. Questions? Ask in the forum or reply to this email. ";
+const GERMAN: &str = "Über 300 Teilnehmer haben sich für die „Rust & Web“-Konferenz angemeldet. Die Vorträge behandeln Fehlerbehandlung, Nebenläufigkeit und Datenbanken — Anmeldung bis Freitag möglich. ";
+const JAPANESE: &str = "このライブラリは「安全」で高速なウェブアプリケーションを構築するためのツールを提供します。詳しくはドキュメントをご覧ください。";
+const CODE: &str = "if a < b && c > d { println!(\"<{a}> & <{b}>\"); } ";
+
+fn realistic_text(c: &mut Criterion) {
+    let mut group = c.benchmark_group("escape");
+    for (name, paragraph) in [("english", ENGLISH), ("german", GERMAN), ("japanese", JAPANESE), ("code", CODE)] {
+        let text = paragraph.repeat(64 * 1024 / paragraph.len());
+        assert_eq!(text.to_html().0, reference::escape_html(&text));
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::new("byte loop", name), &*text, |b, text| b.iter(|| reference::escape_html(text)));
+        group.bench_with_input(BenchmarkId::new("memchr", name), &*text, |b, text| b.iter(|| text.to_html()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, realistic_text);
+criterion_main!(benches);
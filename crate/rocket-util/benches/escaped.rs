@@ -0,0 +1,77 @@
+use {
+    std::fmt,
+    criterion::{
+        BenchmarkId,
+        Criterion,
+        criterion_group,
+        criterion_main,
+    },
+    rocket::response::content::RawHtml,
+    rocket_util::{
+        Escaped,
+        write_html,
+    },
+};
+
+struct Price {
+    cents: u64,
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02} €", self.cents / 100, self.cents % 100)
+    }
+}
+
+struct Row {
+    id: u64,
+    ratio: f64,
+    price: Price,
+}
+
+fn rows(len: u64) -> Vec<Row> {
+    (0..len).map(|id| Row { id, ratio: id as f64 / 7.0, price: Price { cents: id * 1_234 } }).collect()
+}
+
+/// Formats each cell into a temporary string which is then escaped, like `ToHtml` did for `Display` types before [`Escaped`].
+fn via_to_string(rows: &[Row]) -> RawHtml<String> {
+    let mut buf = RawHtml(String::default());
+    write_html!(buf, table {
+        @for row in rows {
+            tr {
+                td : row.id.to_string();
+                td : row.ratio.to_string();
+                td : row.price.to_string();
+            }
+        }
+    });
+    buf
+}
+
+fn via_escaped(rows: &[Row]) -> RawHtml<String> {
+    let mut buf = RawHtml(String::default());
+    write_html!(buf, table {
+        @for row in rows {
+            tr {
+                td : Escaped(row.id);
+                td : Escaped(row.ratio);
+                td : Escaped(&row.price);
+            }
+        }
+    });
+    buf
+}
+
+fn tables(c: &mut Criterion) {
+    let mut group = c.benchmark_group("table");
+    for len in [1_000, 100_000] {
+        let rows = rows(len);
+        assert_eq!(via_to_string(&rows).0, via_escaped(&rows).0);
+        group.bench_with_input(BenchmarkId::new("to_string", len), &rows, |b, rows| b.iter(|| via_to_string(rows)));
+        group.bench_with_input(BenchmarkId::new("Escaped", len), &rows, |b, rows| b.iter(|| via_escaped(rows)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tables);
+criterion_main!(benches);
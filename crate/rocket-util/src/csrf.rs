@@ -0,0 +1,489 @@
+use {
+    std::{
+        borrow::Cow,
+        convert::Infallible as Never,
+        ops::{
+            Deref,
+            DerefMut,
+        },
+        sync::{
+            Arc,
+            atomic::{
+                self,
+                AtomicBool,
+            },
+        },
+    },
+    base64::{
+        Engine as _,
+        engine::general_purpose::URL_SAFE_NO_PAD,
+    },
+    hmac::{
+        Hmac,
+        Mac as _,
+    },
+    rand::RngCore as _,
+    rocket::{
+        Build,
+        Data,
+        Rocket,
+        Route,
+        data::{
+            self,
+            FromData,
+        },
+        fairing::{
+            self,
+            Fairing,
+            Info,
+            Kind,
+        },
+        form::{
+            self,
+            Contextual,
+            Form,
+        },
+        http::{
+            ContentType,
+            Cookie,
+            Header,
+            Method,
+            Status,
+        },
+        local::asynchronous::Client,
+        outcome::try_outcome,
+        request::{
+            self,
+            FromRequest,
+            Request,
+        },
+        response::Response,
+        route::{
+            self,
+            Handler,
+        },
+    },
+    sha2::Sha256,
+    url::Url,
+    crate::CsrfForm,
+};
+
+const COOKIE_NAME: &str = "csrf_key";
+const HEADER_NAME: &str = "X-CSRF-Token";
+const PROBE_HEADER_NAME: &str = "X-Rocket-Util-CSRF-Probe";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const MAC_LEN: usize = 32;
+/// The length of an authenticity token after base64 encoding.
+const TOKEN_LEN: usize = (SALT_LEN + MAC_LEN) * 4 / 3;
+/// How much of a form body [`CsrfFairing::enforce`] reads to find the authenticity token. This is the most Rocket allows to be peeked.
+const PEEK_LEN: usize = 512;
+/// The name of the route which [`CsrfFairing::enforce`] uses to reject requests.
+const REJECT_ROUTE_NAME: &str = "rocket_util_csrf_reject";
+
+fn decode_key(value: &str) -> Option<[u8; KEY_LEN]> {
+    URL_SAFE_NO_PAD.decode(value).ok()?.try_into().ok()
+}
+
+fn mac(key: &[u8; KEY_LEN], salt: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(salt);
+    mac
+}
+
+fn verify_token(key: &[u8; KEY_LEN], authenticity_token: &[u8]) -> Result<(), CsrfVerificationFailure> {
+    let token = URL_SAFE_NO_PAD.decode(authenticity_token).map_err(|_| CsrfVerificationFailure)?;
+    if token.len() != SALT_LEN + MAC_LEN { return Err(CsrfVerificationFailure) }
+    let (salt, tag) = token.split_at(SALT_LEN);
+    mac(key, salt).verify_slice(tag).map_err(|_| CsrfVerificationFailure)
+}
+
+/// Checks whether the given start of a form body contains an authenticity token for the given key. Since the name of the token field depends on the form, this looks for any value which is a valid token, which works for both URL-encoded and multipart forms.
+fn body_contains_token(key: &[u8; KEY_LEN], body: &[u8]) -> bool {
+    body.split(|&b| !matches!(b, b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_'))
+        .filter(|word| word.len() == TOKEN_LEN)
+        .any(|word| verify_token(key, word).is_ok())
+}
+
+fn is_state_changing_method(method: Method) -> bool {
+    matches!(method, Method::Post | Method::Put | Method::Patch | Method::Delete)
+}
+
+fn is_state_changing(req: &Request<'_>) -> bool {
+    is_state_changing_method(req.method())
+}
+
+fn is_form(req: &Request<'_>) -> bool {
+    req.content_type().is_some_and(|content_type| content_type.is_form() || content_type.is_form_data())
+}
+
+/// Checks whether the given route could handle the given request, ignoring its format and rank. Used to apply [`CsrfFairing::exempt`] before routing.
+fn could_match(route: &Route, req: &Request<'_>) -> bool {
+    if route.method != req.method() { return false }
+    let mut segments = req.uri().path().segments();
+    for route_segment in route.uri.path().split('/').filter(|segment| !segment.is_empty()) {
+        if route_segment.starts_with('<') && route_segment.ends_with("..>") { return true }
+        let Some(segment) = segments.next() else { return false };
+        if !route_segment.starts_with('<') && route_segment != segment { return false }
+    }
+    segments.next().is_none() && route.uri.query().is_none_or(|query| query.split('&')
+        .filter(|field| !field.starts_with('<'))
+        .all(|field| req.uri().query().is_some_and(|req_query| req_query.raw_segments().any(|req_field| req_field == field)))
+    )
+}
+
+/// Set by [`CsrfFairing`] if a state-changing request failed the origin check.
+struct CrossOrigin(bool);
+
+/// The client's CSRF key, set by [`CsrfFairing`]. [`CsrfToken`] only reads the key from here, since a cookie which isn't known to be private could have been set by an attacker.
+struct CsrfKey(Option<[u8; KEY_LEN]>);
+
+/// Set once a [`CsrfToken`] has been used to check the request.
+#[derive(Default)]
+struct Checked(Arc<AtomicBool>);
+
+/// Set by [`CsrfFairing`] if a request is to be rejected by [`CsrfFairing::enforce`]. Can be cleared by [`ProbeFairing`] so [`unprotected_routes`] still reaches the routes.
+#[derive(Default)]
+struct Rejected(AtomicBool);
+
+/// The routes exempted from enforcement, managed by [`CsrfFairing`] so [`unprotected_routes`] can skip them.
+struct ExemptRoutes(Vec<Cow<'static, str>>);
+
+/// The highest-ranked route for each state-changing method when enforcement is enabled. Rejects requests marked by [`CsrfFairing`] and forwards all others.
+#[derive(Clone)]
+struct RejectHandler;
+
+#[rocket::async_trait]
+impl Handler for RejectHandler {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        if req.local_cache(Rejected::default).0.load(atomic::Ordering::SeqCst) {
+            route::Outcome::Error(Status::Forbidden)
+        } else {
+            route::Outcome::Forward((data, Status::NotFound))
+        }
+    }
+}
+
+/// A fairing which makes sure each client has a random CSRF key, stored in a private cookie (i.e. encrypted and authenticated using Rocket's secret key).
+///
+/// Since the cookie is private, the `secret_key` configuration parameter should be set for tokens to remain valid across restarts.
+///
+/// If a site origin is configured using [`CsrfFairing::site_origin`], the `Origin` and `Sec-Fetch-Site` headers of `POST`, `PUT`, `PATCH`, and `DELETE` requests are checked as an additional layer of defense. Requests failing this check are not rejected outright, but [`CsrfToken::verify`] will fail for them.
+///
+/// Routes should verify form submissions before their handler runs using the [`CsrfProtected`] data guard. Use [`unprotected_routes`] in a test to make sure none are missed.
+///
+/// If enforcement is enabled using [`CsrfFairing::enforce`], every `POST`, `PUT`, `PATCH`, or `DELETE` request with a form body must contain a valid authenticity token, either as the value of any field within the first 512 bytes of the body or in the `X-CSRF-Token` header, unless it could be handled by a route exempted using [`CsrfFairing::exempt`]. Other requests are rejected with [`Status::Forbidden`] before routing, so no handler runs for them.
+#[derive(Default)]
+pub struct CsrfFairing {
+    site_origin: Option<url::Origin>,
+    trusted_origins: Vec<url::Origin>,
+    enforce: bool,
+    exempt_routes: Vec<Cow<'static, str>>,
+}
+
+impl CsrfFairing {
+    /// Enables the origin check, with the origin of the given URL as the expected origin.
+    pub fn site_origin(mut self, url: &Url) -> Self {
+        self.site_origin = Some(url.origin());
+        self
+    }
+
+    /// Allows cross-origin requests from the origin of the given URL to pass the origin check.
+    pub fn trust_origin(mut self, url: &Url) -> Self {
+        self.trusted_origins.push(url.origin());
+        self
+    }
+
+    /// Enables enforcement of CSRF checks for form submissions. This mounts a catch-all route with the highest possible rank for each state-changing method, which responds with [`Status::Forbidden`] to rejected requests and forwards all others.
+    pub fn enforce(mut self) -> Self {
+        self.enforce = true;
+        self
+    }
+
+    /// Exempts the route with the given name (by default the name of the handler function) from enforcement and from [`unprotected_routes`].
+    ///
+    /// Since enforcement happens before routing, a request is exempted if the path, method, and static query fields of an exempted route match, even if another route ends up handling it.
+    pub fn exempt(mut self, route_name: impl Into<Cow<'static, str>>) -> Self {
+        self.exempt_routes.push(route_name.into());
+        self
+    }
+
+    fn is_cross_origin(&self, req: &Request<'_>) -> bool {
+        let Some(ref site_origin) = self.site_origin else { return false };
+        if !is_state_changing(req) { return false }
+        if let Some(origin) = req.headers().get_one("Origin") {
+            // opaque origins, e.g. `null`, are never equal to anything
+            let origin = Url::parse(origin).map_or_else(|_| url::Origin::new_opaque(), |url| url.origin());
+            origin != *site_origin && !self.trusted_origins.contains(&origin)
+        } else if let Some(site) = req.headers().get_one("Sec-Fetch-Site") {
+            !matches!(site, "same-origin" | "none")
+        } else {
+            // not sent by browsers that predate these headers, or by non-browser clients
+            false
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for CsrfFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "CSRF",
+            kind: Kind::Ignite | Kind::Request,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let mut rocket = rocket.manage(ExemptRoutes(self.exempt_routes.clone()));
+        if self.enforce {
+            rocket = rocket.mount("/", [Method::Post, Method::Put, Method::Patch, Method::Delete].into_iter().map(|method| {
+                let mut route = Route::ranked(isize::MIN, method, "/<_..>", RejectHandler);
+                route.name = Some(Cow::Borrowed(REJECT_ROUTE_NAME));
+                route
+            }).collect::<Vec<_>>());
+        }
+        Ok(rocket)
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, data: &mut Data<'_>) {
+        let cross_origin = self.is_cross_origin(req);
+        req.local_cache(|| CrossOrigin(cross_origin));
+        let cookies = req.cookies();
+        let key = cookies.get_private(COOKIE_NAME).and_then(|cookie| decode_key(cookie.value())).unwrap_or_else(|| {
+            let mut key = [0; KEY_LEN];
+            rand::thread_rng().fill_bytes(&mut key);
+            cookies.add_private(Cookie::new(COOKIE_NAME, URL_SAFE_NO_PAD.encode(key)));
+            key
+        });
+        req.local_cache(|| CsrfKey(Some(key)));
+        if !self.enforce || !is_state_changing(req) || !is_form(req) { return }
+        if req.rocket().routes().any(|route| route.name.as_ref().is_some_and(|name| self.exempt_routes.contains(name)) && could_match(route, req)) { return }
+        let valid = !cross_origin && (
+            req.headers().get_one(HEADER_NAME).is_some_and(|token| verify_token(&key, token.as_bytes()).is_ok())
+            || body_contains_token(&key, data.peek(PEEK_LEN).await)
+        );
+        if !valid {
+            eprintln!("rejecting {} request to {} without a valid CSRF token", req.method(), req.uri());
+            req.local_cache(Rejected::default).0.store(true, atomic::Ordering::SeqCst);
+        }
+    }
+}
+
+/// The error returned by [`CsrfToken::verify`].
+#[derive(Debug, thiserror::Error)]
+#[error("CSRF token verification failed")]
+pub struct CsrfVerificationFailure;
+
+/// A request guard which can generate and verify authenticity tokens for the current client. Tokens are HMAC-signed using the client's CSRF key.
+///
+/// Requires [`CsrfFairing`] to be attached. Otherwise, the guard forwards with [`Status::Forbidden`].
+pub struct CsrfToken {
+    key: [u8; KEY_LEN],
+    authenticity_token: String,
+    cross_origin: bool,
+    checked: Arc<AtomicBool>,
+    accept_language: Option<String>,
+}
+
+impl CsrfToken {
+    fn new(key: [u8; KEY_LEN], cross_origin: bool, checked: Arc<AtomicBool>, accept_language: Option<String>) -> Self {
+        let mut token = [0; SALT_LEN + MAC_LEN];
+        rand::thread_rng().fill_bytes(&mut token[..SALT_LEN]);
+        let (salt, tag) = token.split_at_mut(SALT_LEN);
+        tag.copy_from_slice(&mac(&key, salt).finalize().into_bytes());
+        Self {
+            authenticity_token: URL_SAFE_NO_PAD.encode(token),
+            key,
+            cross_origin,
+            checked,
+            accept_language,
+        }
+    }
+
+    /// A token which can be submitted along with a form. A different token is generated for each request to mitigate [BREACH](https://en.wikipedia.org/wiki/BREACH).
+    pub fn authenticity_token(&self) -> &str {
+        &self.authenticity_token
+    }
+
+    /// The value of the `Accept-Language` header of the request for which this token was generated, for localizing error messages.
+    pub fn accept_language(&self) -> Option<&str> {
+        self.accept_language.as_deref()
+    }
+
+    /// Checks whether the given token was generated for the same client. Always fails if the request failed the origin check configured on [`CsrfFairing`].
+    pub fn verify(&self, form_authenticity_token: &str) -> Result<(), CsrfVerificationFailure> {
+        self.mark_checked();
+        if self.cross_origin { return Err(CsrfVerificationFailure) }
+        verify_token(&self.key, form_authenticity_token.as_bytes())
+    }
+
+    /// Marks the request as checked for the purpose of [`unprotected_routes`] without verifying a token, e.g. because the form could not be parsed.
+    pub(crate) fn mark_checked(&self) {
+        self.checked.store(true, atomic::Ordering::SeqCst);
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CsrfToken {
+    type Error = Never;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, Never> {
+        match req.local_cache(|| CsrfKey(None)).0 {
+            Some(key) => request::Outcome::Success(Self::new(
+                key,
+                req.local_cache(|| CrossOrigin(false)).0,
+                req.local_cache(Checked::default).0.clone(),
+                req.headers().get_one("Accept-Language").map(str::to_owned),
+            )),
+            None => request::Outcome::Forward(Status::Forbidden),
+        }
+    }
+}
+
+/// A request guard which verifies the authenticity token sent in the `X-CSRF-Token` header, for use with `fetch` or XHR requests where [`ContextualExt::verify`](crate::ContextualExt::verify) isn't applicable.
+///
+/// Fails with [`Status::Forbidden`] if the header is missing or the token is invalid. Requires [`CsrfFairing`] to be attached. The token can be made available to client-side scripts using [`CsrfMeta`](crate::CsrfMeta).
+pub struct CsrfHeader;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for CsrfHeader {
+    type Error = CsrfVerificationFailure;
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, CsrfVerificationFailure> {
+        let Some(header) = req.headers().get_one(HEADER_NAME) else { return request::Outcome::Error((Status::Forbidden, CsrfVerificationFailure)) };
+        match req.guard::<CsrfToken>().await {
+            request::Outcome::Success(token) => match token.verify(header) {
+                Ok(()) => request::Outcome::Success(Self),
+                Err(e) => request::Outcome::Error((Status::Forbidden, e)),
+            },
+            request::Outcome::Error((_, never)) => match never {},
+            request::Outcome::Forward(_) => request::Outcome::Error((Status::Forbidden, CsrfVerificationFailure)),
+        }
+    }
+}
+
+/// A data guard which parses a form like [`Contextual`] and verifies its CSRF token before the handler runs.
+///
+/// If verification fails, the form's `value` is `None` and its `context` contains an error for the token field with the message from [`CsrfForm::csrf_error_message`], so the form can be displayed again. Requires [`CsrfFairing`] to be attached. Otherwise, verification always fails.
+pub struct CsrfProtected<'r, F>(pub Contextual<'r, F>);
+
+impl<'r, F> CsrfProtected<'r, F> {
+    pub fn into_inner(self) -> Contextual<'r, F> {
+        self.0
+    }
+}
+
+impl<'r, F> Deref for CsrfProtected<'r, F> {
+    type Target = Contextual<'r, F>;
+
+    fn deref(&self) -> &Contextual<'r, F> {
+        &self.0
+    }
+}
+
+impl<F> DerefMut for CsrfProtected<'_, F> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, F: CsrfForm + form::FromForm<'r>> FromData<'r> for CsrfProtected<'r, F> {
+    type Error = form::Errors<'r>;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        // lets `unprotected_routes` know that this route is protected even if the form can't be parsed
+        req.local_cache(Checked::default).0.store(true, atomic::Ordering::SeqCst);
+        let mut form = try_outcome!(Form::<Contextual<'r, F>>::from_data(req, data).await).into_inner();
+        let token = req.guard::<CsrfToken>().await.succeeded();
+        if !crate::verify_contextual(&mut form, &token, req.headers().get_one("Accept-Language")) {
+            form.value = None;
+        }
+        data::Outcome::Success(Self(form))
+    }
+}
+
+/// Reports whether the route which handled a probe from [`unprotected_routes`] checked the CSRF token.
+struct ProbeFairing;
+
+#[rocket::async_trait]
+impl Fairing for ProbeFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "CSRF probe",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        // let probes through to the routes even if CsrfFairing::enforce is enabled, since they don't have a valid token
+        if req.headers().contains(PROBE_HEADER_NAME) {
+            req.local_cache(Rejected::default).0.store(false, atomic::Ordering::SeqCst);
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(probe) = req.headers().get_one(PROBE_HEADER_NAME) else { return };
+        // only report a result for the probed route, not for other routes the request was forwarded to
+        if req.route().is_some_and(|route| probe_id(route) == probe) && req.local_cache(Checked::default).0.load(atomic::Ordering::SeqCst) {
+            res.set_raw_header(PROBE_HEADER_NAME, "checked");
+        }
+    }
+}
+
+fn probe_id(route: &Route) -> String {
+    format!("{} {} {}", route.method, route.uri, route.rank)
+}
+
+/// Returns a URI which matches the given route, using `0` for dynamic parameters.
+fn probe_uri(route: &Route) -> String {
+    let mut uri = String::default();
+    for segment in route.uri.path().split('/').filter(|segment| !segment.is_empty()) {
+        uri.push('/');
+        uri.push_str(if segment.starts_with('<') { "0" } else { segment });
+    }
+    if uri.is_empty() || route.uri.path().ends_with('/') {
+        uri.push('/');
+    }
+    if let Some(query) = route.uri.query() {
+        let fields = query.split('&')
+            .filter(|field| !field.ends_with("..>"))
+            .map(|field| if let Some(name) = field.strip_prefix('<') { format!("{}=0", name.trim_end_matches('>')) } else { field.to_owned() })
+            .collect::<Vec<_>>();
+        if !fields.is_empty() {
+            uri.push('?');
+            uri.push_str(&fields.join("&"));
+        }
+    }
+    uri
+}
+
+/// Returns the `POST`, `PUT`, `PATCH`, and `DELETE` routes which don't check a CSRF token before their handler runs, e.g. using [`CsrfProtected`] or [`CsrfHeader`], and aren't exempted using [`CsrfFairing::exempt`]. Intended for use in a test.
+///
+/// This launches the given Rocket instance as a local client and sends a form submission to each route, using `0` for dynamic parameters. The form data limits are lowered to zero, so form data guards reject the submission before the handler runs. Handlers of routes without a form data guard do run, with an empty form. Routes which don't accept the submission, e.g. because a path parameter can't be `0`, are also returned, since they can't be shown to be protected.
+///
+/// # Errors
+///
+/// If the Rocket instance fails to launch.
+pub async fn unprotected_routes(rocket: Rocket<Build>) -> Result<Vec<Route>, rocket::Error> {
+    let figment = rocket.figment().clone()
+        .merge(("limits.form", 0))
+        .merge(("limits.data-form", 0));
+    let client = Client::tracked(rocket.configure(figment).attach(ProbeFairing)).await?;
+    let exempt_routes = client.rocket().state::<ExemptRoutes>().map(|ExemptRoutes(routes)| &routes[..]).unwrap_or_default();
+    let mut unprotected = Vec::default();
+    for route in client.rocket().routes() {
+        if !is_state_changing_method(route.method) { continue }
+        if route.name.as_ref().is_some_and(|name| name == REJECT_ROUTE_NAME || exempt_routes.contains(name)) { continue }
+        let response = client.req(route.method, probe_uri(route))
+            .header(ContentType::Form)
+            .header(Header::new(HEADER_NAME, "probe"))
+            .header(Header::new(PROBE_HEADER_NAME, probe_id(route)))
+            .body("probe")
+            .dispatch().await;
+        if response.headers().get_one(PROBE_HEADER_NAME) != Some("checked") {
+            unprotected.push(route.clone());
+        }
+    }
+    Ok(unprotected)
+}
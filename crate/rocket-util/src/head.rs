@@ -0,0 +1,122 @@
+use {
+    std::cell::RefCell,
+    rocket::response::content::RawHtml,
+    rocket_util_derive::html_internal,
+    crate::{
+        ToHtml,
+        ToHtmlUrl,
+    },
+};
+
+#[derive(PartialEq, Eq)]
+enum MetaKey {
+    Name(String),
+    Property(String),
+}
+
+#[derive(Default)]
+struct HeadEntries {
+    title: Option<RawHtml<String>>,
+    meta: Vec<(MetaKey, RawHtml<String>)>,
+    stylesheets: Vec<RawHtml<String>>,
+    scripts: Vec<RawHtml<String>>,
+}
+
+/// Collects entries for the `<head>` of a page from fragments rendered anywhere on it, e.g. a component which needs a stylesheet.
+///
+/// Pass a reference to components which need it, then assemble the page using [`Head::finish`]. Entries are rendered in a fixed order: the title, then `<meta>` tags, then stylesheets, then scripts, each in the order in which they were first added.
+#[derive(Default)]
+pub struct Head(RefCell<HeadEntries>);
+
+impl Head {
+    /// Sets the `<title>` of the page, replacing any previously set title. The page's layout should not have a `title` element of its own.
+    pub fn title(&self, title: impl ToHtml) {
+        self.0.borrow_mut().title = Some(title.to_html());
+    }
+
+    /// Adds a `<meta name="..." content="...">` tag, e.g. for a description. If a tag with the same name was already added, its content is replaced.
+    pub fn meta(&self, name: &str, content: impl ToHtml) {
+        let tag = html_internal! {
+            meta(name = name, content = content);
+        };
+        self.set_meta(MetaKey::Name(name.to_owned()), tag);
+    }
+
+    /// Adds a `<meta property="..." content="...">` tag, e.g. for [OpenGraph](https://ogp.me/). If a tag with the same property was already added, its content is replaced.
+    pub fn property(&self, property: &str, content: impl ToHtml) {
+        let tag = html_internal! {
+            meta(property = property, content = content);
+        };
+        self.set_meta(MetaKey::Property(property.to_owned()), tag);
+    }
+
+    fn set_meta(&self, key: MetaKey, tag: RawHtml<String>) {
+        let meta = &mut self.0.borrow_mut().meta;
+        if let Some((_, existing)) = meta.iter_mut().find(|(existing, _)| *existing == key) {
+            *existing = tag;
+        } else {
+            meta.push((key, tag));
+        }
+    }
+
+    /// Adds a `<link rel="stylesheet">` tag, unless one with the same URL was already added.
+    pub fn stylesheet(&self, url: impl ToHtmlUrl) {
+        let tag = html_internal! {
+            link(rel = "stylesheet", href = url);
+        };
+        let stylesheets = &mut self.0.borrow_mut().stylesheets;
+        if !stylesheets.contains(&tag) {
+            stylesheets.push(tag);
+        }
+    }
+
+    /// Adds a `<script defer>` tag, unless one with the same URL was already added.
+    pub fn script(&self, url: impl ToHtmlUrl) {
+        let tag = html_internal! {
+            script(src = url, defer);
+        };
+        let scripts = &mut self.0.borrow_mut().scripts;
+        if !scripts.contains(&tag) {
+            scripts.push(tag);
+        }
+    }
+
+    /// Inserts the collected entries at the end of the `<head>` of the given page. This also clears the collected entries.
+    ///
+    /// If the page has no `</head>` tag, the entries are inserted right after the `<head>` tag, or if there is none, at the start of the page (after the doctype, if any). Since the whole page is needed to find the insertion point, this can't be used with [`html_stream!`](crate::html_stream!) or [`write_html!`](crate::write_html!).
+    pub fn finish(&self, page: RawHtml<String>) -> RawHtml<String> {
+        let HeadEntries { title, meta, stylesheets, scripts } = self.0.take();
+        let RawHtml(mut page) = page;
+        let entries = html_internal! {
+            @if let Some(title) = title {
+                title : title;
+            }
+            @for (_, tag) in meta {
+                : tag;
+            }
+            @for tag in stylesheets {
+                : tag;
+            }
+            @for tag in scripts {
+                : tag;
+            }
+        };
+        page.insert_str(insertion_point(&page), &entries.0);
+        RawHtml(page)
+    }
+}
+
+/// Finds the position in the page at which [`Head::finish`] inserts entries.
+fn insertion_point(page: &str) -> usize {
+    page.find("</head>")
+        .or_else(|| page.match_indices("<head")
+            .find(|(start, _)| matches!(page[start + 5..].chars().next(), Some('>' | ' ')))
+            .and_then(|(start, _)| Some(start + page[start..].find('>')? + 1))
+        )
+        .or_else(|| page.get(..9)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("<!doctype"))
+            .then(|| Some(page.find('>')? + 1))
+            .flatten()
+        )
+        .unwrap_or(0)
+}
@@ -29,22 +29,225 @@ use {
     std::{
         borrow::Cow,
         convert::Infallible as Never,
-        fmt::Write as _,
+        fmt::{
+            self,
+            Write as _,
+        },
+        hash::Hasher,
+        io,
         num::NonZero,
     },
+    memchr::{
+        memchr,
+        memchr3,
+    },
     rocket::response::content::RawHtml,
 };
-#[cfg(feature = "rocket_csrf")] use {
-    rocket_csrf::CsrfToken,
+#[cfg(feature = "serde_json")] use serde::Serialize;
+#[cfg(feature = "csrf")] use {
     rocket_util_derive::html_internal,
+    crate::{
+        CsrfForm,
+        CsrfToken,
+    },
 };
 
+/// A destination for rendered HTML, e.g. a buffer, a streamed response body, or a hasher for computing an `ETag`.
+///
+/// The [`html`](crate::html!) macro renders into a [`RawHtml<String>`], while the [`write_html`](crate::write_html!) macro renders into any `HtmlWriter`.
+pub trait HtmlWriter {
+    /// Writes HTML which has already been escaped.
+    fn write_raw(&mut self, html: &str);
+
+    /// Passes the HTML written so far on to its destination, for writers which buffer it. Returns `false` if rendering should stop, e.g. because the client has disconnected.
+    fn flush(&mut self) -> bool {
+        true
+    }
+
+    /// Returns the underlying buffer if this writer is a [`RawHtml<String>`]. This allows the default implementation of [`ToHtml::write_html`] to use [`ToHtml::push_html`].
+    fn as_raw_html(&mut self) -> Option<&mut RawHtml<String>> {
+        None
+    }
+}
+
+impl HtmlWriter for RawHtml<String> {
+    fn write_raw(&mut self, html: &str) {
+        self.0.push_str(html);
+    }
+
+    fn as_raw_html(&mut self) -> Option<&mut RawHtml<String>> {
+        Some(self)
+    }
+}
+
+impl HtmlWriter for String {
+    fn write_raw(&mut self, html: &str) {
+        self.push_str(html);
+    }
+}
+
+impl HtmlWriter for Vec<u8> {
+    fn write_raw(&mut self, html: &str) {
+        self.extend_from_slice(html.as_bytes());
+    }
+}
+
+impl<W: HtmlWriter + ?Sized> HtmlWriter for &mut W {
+    fn write_raw(&mut self, html: &str) {
+        (**self).write_raw(html);
+    }
+
+    fn flush(&mut self) -> bool {
+        (**self).flush()
+    }
+
+    fn as_raw_html(&mut self) -> Option<&mut RawHtml<String>> {
+        (**self).as_raw_html()
+    }
+}
+
+/// Writes HTML to an [`io::Write`]. After an I/O error, further HTML is discarded and [`HtmlWriter::flush`] returns `false`.
+pub struct IoWriter<W: io::Write> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    /// Returns the underlying writer, or the first I/O error that occurred.
+    pub fn into_inner(self) -> io::Result<W> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.inner),
+        }
+    }
+}
+
+impl<W: io::Write> HtmlWriter for IoWriter<W> {
+    fn write_raw(&mut self, html: &str) {
+        if self.error.is_none() && let Err(e) = self.inner.write_all(html.as_bytes()) {
+            self.error = Some(e);
+        }
+    }
+
+    fn flush(&mut self) -> bool {
+        if self.error.is_none() && let Err(e) = self.inner.flush() {
+            self.error = Some(e);
+        }
+        self.error.is_none()
+    }
+}
+
+/// Feeds HTML into a [`Hasher`], e.g. to compute an `ETag` without keeping the rendered HTML in memory.
+///
+/// With some hashers, the result depends on how the HTML is split into writes, but it's deterministic for the same rendering code.
+pub struct HashWriter<H: Hasher>(pub H);
+
+impl<H: Hasher> HtmlWriter for HashWriter<H> {
+    fn write_raw(&mut self, html: &str) {
+        self.0.write(html.as_bytes());
+    }
+}
+
+/// Calls `f` with a buffer whose contents are then written to `w`. If `w` is a [`RawHtml<String>`], `f` writes to it directly.
+fn write_via_string(w: &mut dyn HtmlWriter, f: impl FnOnce(&mut String)) {
+    if let Some(buf) = w.as_raw_html() {
+        f(&mut buf.0);
+    } else {
+        let mut buf = String::default();
+        f(&mut buf);
+        w.write_raw(&buf);
+    }
+}
+
+/// Returns the index of the first byte which needs to be escaped in HTML.
+fn next_special(bytes: &[u8]) -> Option<usize> {
+    // special bytes are often close together, e.g. in code samples, where scanning a few bytes directly is faster than calling memchr
+    let (head, tail) = bytes.split_at(bytes.len().min(16));
+    if let Some(idx) = head.iter().position(|b| matches!(b, b'"' | b'&' | b'<' | b'>')) { return Some(idx) }
+    let quot_amp_lt = memchr3(b'"', b'&', b'<', tail);
+    // only search up to the first match of the other three so each byte is scanned at most twice
+    memchr(b'>', &tail[..quot_amp_lt.unwrap_or(tail.len())]).or(quot_amp_lt).map(|idx| head.len() + idx)
+}
+
+/// Writes text to `w`, escaped for use in element content or a double-quoted attribute value. Runs of text which don't need escaping are written in one piece.
+fn escape_into(text: &str, w: &mut dyn HtmlWriter) {
+    if let Some(buf) = w.as_raw_html() {
+        // avoids a dynamic call for each run, which dominates for text with many special characters
+        buf.0.reserve(text.len());
+        escape_runs(text, |run| buf.0.push_str(run));
+    } else {
+        escape_runs(text, |run| w.write_raw(run));
+    }
+}
+
+fn escape_runs(text: &str, mut write: impl FnMut(&str)) {
+    let mut start = 0;
+    while let Some(offset) = next_special(&text.as_bytes()[start..]) {
+        let idx = start + offset;
+        let escaped = match text.as_bytes()[idx] {
+            b'"' => "&quot;",
+            b'&' => "&amp;",
+            b'<' => "&lt;",
+            b'>' => "&gt;",
+            _ => unreachable!(),
+        };
+        if start < idx {
+            write(&text[start..idx]);
+        }
+        write(escaped);
+        start = idx + 1;
+    }
+    if start < text.len() {
+        write(&text[start..]);
+    }
+}
+
+/// A [`fmt::Write`] which escapes everything written to it for use in element content or a double-quoted attribute value.
+struct EscapingWriter<'a>(&'a mut dyn HtmlWriter);
+
+impl fmt::Write for EscapingWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        escape_into(s, self.0);
+        Ok(())
+    }
+}
+
 pub trait ToHtml {
     fn to_html(&self) -> RawHtml<String>;
 
     fn push_html(&self, buf: &mut RawHtml<String>) {
         buf.0.push_str(&self.to_html().0);
     }
+
+    /// Writes this value as HTML to the given writer. This is what the [`html`](crate::html!) macro uses.
+    ///
+    /// The default implementation uses [`ToHtml::push_html`] if the writer is a [`RawHtml<String>`] and [`ToHtml::to_html`] otherwise. Types which implement this method should implement the other two in terms of it, e.g. using [`ToHtml::write_html`] on a [`RawHtml<String>`].
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        if let Some(buf) = w.as_raw_html() {
+            self.push_html(buf);
+        } else {
+            w.write_raw(&self.to_html().0);
+        }
+    }
+}
+
+/// Implements [`ToHtml::to_html`] and [`ToHtml::push_html`] in terms of [`ToHtml::write_html`].
+macro_rules! via_write_html {
+    () => {
+        fn to_html(&self) -> RawHtml<String> {
+            let mut buf = RawHtml(String::default());
+            self.write_html(&mut buf);
+            buf
+        }
+
+        fn push_html(&self, buf: &mut RawHtml<String>) {
+            self.write_html(buf);
+        }
+    };
 }
 
 impl<T: ToString> ToHtml for RawHtml<T> {
@@ -55,39 +258,17 @@ impl<T: ToString> ToHtml for RawHtml<T> {
     fn push_html(&self, buf: &mut RawHtml<String>) {
         buf.0.push_str(&self.0.to_string());
     }
-}
 
-impl<'a> ToHtml for &'a str {
-    fn to_html(&self) -> RawHtml<String> {
-        let mut escaped = Vec::with_capacity(self.len());
-        for b in self.bytes() {
-            match b {
-                b'"' => escaped.extend_from_slice(b"&quot;"),
-                b'&' => escaped.extend_from_slice(b"&amp;"),
-                b'<' => escaped.extend_from_slice(b"&lt;"),
-                b'>' => escaped.extend_from_slice(b"&gt;"),
-                _ => escaped.push(b),
-            }
-        }
-        //SAFETY: `escaped` is derived from a valid UTF-8 string, with only ASCII characters replaced with other ASCII characters. Since UTF-8 is self-synchronizing, `escaped` remains valid UTF-8.
-        RawHtml(unsafe { String::from_utf8_unchecked(escaped) })
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        w.write_raw(&self.0.to_string());
     }
+}
 
-    fn push_html(&self, buf: &mut RawHtml<String>) {
-        //SAFETY: `escaped` is derived from a valid UTF-8 string, with only ASCII characters replaced with other ASCII characters. Since UTF-8 is self-synchronizing, `escaped` remains valid UTF-8.
-        unsafe {
-            let escaped = buf.0.as_mut_vec();
-            escaped.reserve(self.len());
-            for b in self.bytes() {
-                match b {
-                    b'"' => escaped.extend_from_slice(b"&quot;"),
-                    b'&' => escaped.extend_from_slice(b"&amp;"),
-                    b'<' => escaped.extend_from_slice(b"&lt;"),
-                    b'>' => escaped.extend_from_slice(b"&gt;"),
-                    _ => escaped.push(b),
-                }
-            }
-        }
+impl ToHtml for &str {
+    via_write_html!();
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        escape_into(self, w);
     }
 }
 
@@ -99,9 +280,13 @@ impl ToHtml for String {
     fn push_html(&self, buf: &mut RawHtml<String>) {
         (&**self).push_html(buf);
     }
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        (&**self).write_html(w);
+    }
 }
 
-impl<'a, T: ToHtml> ToHtml for &'a T {
+impl<T: ToHtml> ToHtml for &T {
     fn to_html(&self) -> RawHtml<String> {
         (*self).to_html()
     }
@@ -109,6 +294,10 @@ impl<'a, T: ToHtml> ToHtml for &'a T {
     fn push_html(&self, buf: &mut RawHtml<String>) {
         (*self).push_html(buf);
     }
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        (*self).write_html(w);
+    }
 }
 
 impl<'a, T: ToOwned + ?Sized> ToHtml for Cow<'a, T>
@@ -126,6 +315,13 @@ where &'a T: ToHtml, T::Owned: ToHtml {
             Self::Owned(owned) => owned.push_html(buf),
         }
     }
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        match self {
+            Self::Borrowed(borrowed) => borrowed.write_html(w),
+            Self::Owned(owned) => owned.write_html(w),
+        }
+    }
 }
 
 impl<T: ToHtml> ToHtml for Option<T> {
@@ -142,6 +338,12 @@ impl<T: ToHtml> ToHtml for Option<T> {
             value.push_html(buf);
         }
     }
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        if let Some(value) = self {
+            value.write_html(w);
+        }
+    }
 }
 
 impl ToHtml for Never {
@@ -152,50 +354,53 @@ impl ToHtml for Never {
     fn push_html(&self, _: &mut RawHtml<String>) {
         match *self {}
     }
+
+    fn write_html(&self, _: &mut dyn HtmlWriter) {
+        match *self {}
+    }
 }
 
 impl ToHtml for char {
-    fn to_html(&self) -> RawHtml<String> {
-        match self {
-            '"' => RawHtml(format!("&quot;")),
-            '&' => RawHtml(format!("&amp;")),
-            '<' => RawHtml(format!("&lt;")),
-            '>' => RawHtml(format!("&gt;")),
-            _ => RawHtml(self.to_string()),
-        }
-    }
+    via_write_html!();
 
-    fn push_html(&self, buf: &mut RawHtml<String>) {
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
         match self {
-            '"' => buf.0.push_str("&quot;"),
-            '&' => buf.0.push_str("&amp;"),
-            '<' => buf.0.push_str("&lt;"),
-            '>' => buf.0.push_str("&gt;"),
-            _ => buf.0.push(*self),
+            '"' => w.write_raw("&quot;"),
+            '&' => w.write_raw("&amp;"),
+            '<' => w.write_raw("&lt;"),
+            '>' => w.write_raw("&gt;"),
+            _ => w.write_raw(self.encode_utf8(&mut [0; 4])),
         }
     }
 }
 
 impl ToHtml for rocket::form::Error<'_> {
-    fn to_html(&self) -> RawHtml<String> {
-        self.to_string().to_html()
+    via_write_html!();
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        Escaped(self).write_html(w);
     }
 }
 
-impl ToHtml for url::Url {
-    fn to_html(&self) -> RawHtml<String> {
-        if self.as_str().contains('"') || self.as_str().contains('&') {
-            self.as_str().to_html()
-        } else {
-            RawHtml(self.to_string())
-        }
+/// Renders a value using its [`Display`](fmt::Display) implementation, escaping it while it's being formatted rather than formatting it into a temporary string first.
+pub struct Escaped<T: fmt::Display>(pub T);
+
+impl<T: fmt::Display> ToHtml for Escaped<T> {
+    via_write_html!();
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        write!(EscapingWriter(w), "{}", self.0).expect("a Display implementation returned an error unexpectedly");
     }
+}
 
-    fn push_html(&self, buf: &mut RawHtml<String>) {
+impl ToHtml for url::Url {
+    via_write_html!();
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
         if self.as_str().contains('"') || self.as_str().contains('&') {
-            self.as_str().push_html(buf);
+            escape_into(self.as_str(), w);
         } else {
-            buf.0.push_str(self.as_str());
+            w.write_raw(self.as_str());
         }
     }
 }
@@ -209,7 +414,11 @@ macro_rules! impl_to_html_unescaped {
                 }
 
                 fn push_html(&self, buf: &mut RawHtml<String>) {
-                    write!(&mut buf.0, "{self}").unwrap();
+                    self.write_html(buf);
+                }
+
+                fn write_html(&self, w: &mut dyn HtmlWriter) {
+                    write_via_string(w, |buf| write!(buf, "{self}").unwrap());
                 }
             }
         )*
@@ -220,12 +429,10 @@ macro_rules! impl_to_html_escaped {
     ($($T:ty),* $(,)?) => {
         $(
             impl ToHtml for $T {
-                fn to_html(&self) -> RawHtml<String> {
-                    self.to_string().to_html()
-                }
+                via_write_html!();
 
-                fn push_html(&self, buf: &mut RawHtml<String>) {
-                    self.to_string().push_html(buf)
+                fn write_html(&self, w: &mut dyn HtmlWriter) {
+                    Escaped(self).write_html(w);
                 }
             }
         )*
@@ -270,7 +477,18 @@ impl_to_html_escaped!(
     rocket::http::uri::Reference<'_>,
 );
 
-#[cfg(feature = "rocket_csrf")]
+#[cfg(feature = "csrf")]
+impl CsrfToken {
+    /// Renders as a hidden `<input>` containing an authenticity token, named after the CSRF token field of the form `F` (see [`CsrfForm::csrf_field_name`]).
+    pub fn input<F: CsrfForm>(&self) -> RawHtml<String> {
+        html_internal! {
+            input(type = "hidden", name = F::csrf_field_name(), value = self.authenticity_token());
+        }
+    }
+}
+
+/// Renders as a hidden `<input>` named `csrf`, which only works with forms using the default name for the CSRF token field. Use [`CsrfToken::input`] for forms which rename it using `#[csrf]` or `#[field(name = "...")]`.
+#[cfg(feature = "csrf")]
 impl ToHtml for CsrfToken {
     fn to_html(&self) -> RawHtml<String> {
         html_internal! {
@@ -281,6 +499,125 @@ impl ToHtml for CsrfToken {
     //TODO specialize push_html for better perf?
 }
 
+/// Renders as a `<meta name="csrf-token">` tag containing an authenticity token, for use in `<head>`.
+///
+/// Client-side scripts can read the token from this tag and send it in the `X-CSRF-Token` header, to be verified using [`CsrfHeader`](crate::CsrfHeader).
+#[cfg(feature = "csrf")]
+pub struct CsrfMeta<'a>(pub &'a CsrfToken);
+
+#[cfg(feature = "csrf")]
+impl ToHtml for CsrfMeta<'_> {
+    fn to_html(&self) -> RawHtml<String> {
+        html_internal! {
+            meta(name = "csrf-token", content = self.0.authenticity_token());
+        }
+    }
+}
+
+/// URL schemes which are allowed by [`ToHtmlUrl`] implementations which check the scheme.
+const SAFE_URL_SCHEMES: [&str; 4] = ["http", "https", "mailto", "tel"];
+
+/// Rendered in place of URLs with a disallowed scheme.
+const UNSAFE_URL_PLACEHOLDER: &str = "about:invalid";
+
+/// Returns the lowercased scheme of the given URL, or `None` if it's a relative URL. Follows the URL parsing algorithm from the WHATWG URL standard, which browsers use.
+fn url_scheme(url: &str) -> Option<String> {
+    let mut scheme = String::default();
+    for c in url.trim_start_matches(|c| c <= ' ').chars().filter(|&c| !matches!(c, '\t' | '\n' | '\r')) {
+        match c {
+            ':' if !scheme.is_empty() => return Some(scheme),
+            'A'..='Z' | 'a'..='z' => scheme.push(c.to_ascii_lowercase()),
+            '0'..='9' | '+' | '-' | '.' if !scheme.is_empty() => scheme.push(c),
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Values which can be used for attributes containing URLs, like `href` or `src`, inside the [`html`](crate::html!) macro.
+///
+/// This excludes plain strings, since URLs with schemes like `javascript:` can be used for cross-site scripting even if correctly escaped. Strings can be wrapped in [`SafeUrl`] to check their scheme, or in [`RawHtml`] if they're trusted.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` can't be used as a URL in `html!`",
+    note = "wrap strings in `rocket_util::SafeUrl` to check their scheme, or in `RawHtml` if they're trusted",
+)]
+pub trait ToHtmlUrl {
+    fn write_html_url(&self, w: &mut dyn HtmlWriter);
+}
+
+impl<T: ToHtmlUrl + ?Sized> ToHtmlUrl for &T {
+    fn write_html_url(&self, w: &mut dyn HtmlWriter) {
+        (**self).write_html_url(w);
+    }
+}
+
+impl<T: ToString> ToHtmlUrl for RawHtml<T> {
+    fn write_html_url(&self, w: &mut dyn HtmlWriter) {
+        self.write_html(w);
+    }
+}
+
+impl ToHtmlUrl for Never {
+    fn write_html_url(&self, _: &mut dyn HtmlWriter) {
+        match *self {}
+    }
+}
+
+impl ToHtmlUrl for crate::Origin<'_> {
+    fn write_html_url(&self, w: &mut dyn HtmlWriter) {
+        self.write_html(w);
+    }
+}
+
+impl ToHtmlUrl for rocket::http::uri::Origin<'_> {
+    fn write_html_url(&self, w: &mut dyn HtmlWriter) {
+        self.write_html(w);
+    }
+}
+
+impl ToHtmlUrl for rocket::http::uri::Absolute<'_> {
+    fn write_html_url(&self, w: &mut dyn HtmlWriter) {
+        if SAFE_URL_SCHEMES.iter().any(|scheme| self.scheme().eq_ignore_ascii_case(scheme)) {
+            self.write_html(w);
+        } else {
+            w.write_raw(UNSAFE_URL_PLACEHOLDER);
+        }
+    }
+}
+
+impl ToHtmlUrl for url::Url {
+    fn write_html_url(&self, w: &mut dyn HtmlWriter) {
+        if SAFE_URL_SCHEMES.contains(&self.scheme()) {
+            self.write_html(w);
+        } else {
+            w.write_raw(UNSAFE_URL_PLACEHOLDER);
+        }
+    }
+}
+
+/// Checks the scheme of a URL given as a string, for use as the value of an attribute like `href` or `src` inside the [`html`](crate::html!) macro.
+///
+/// Relative URLs and URLs with the schemes `http`, `https`, `mailto`, and `tel` are rendered as is (HTML-escaped). Other URLs, e.g. `javascript:` URLs, are replaced with `about:invalid`.
+pub struct SafeUrl<T: AsRef<str>>(pub T);
+
+impl<T: AsRef<str>> ToHtmlUrl for SafeUrl<T> {
+    fn write_html_url(&self, w: &mut dyn HtmlWriter) {
+        if url_scheme(self.0.as_ref()).is_none_or(|scheme| SAFE_URL_SCHEMES.contains(&&*scheme)) {
+            escape_into(self.0.as_ref(), w);
+        } else {
+            w.write_raw(UNSAFE_URL_PLACEHOLDER);
+        }
+    }
+}
+
+impl<T: AsRef<str>> ToHtml for SafeUrl<T> {
+    via_write_html!();
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        self.write_html_url(w);
+    }
+}
+
 /// Members of this trait can be used as the `value` in a `tag(attr? = value)` expression inside the [`html`](crate::html!) macro.
 pub trait OptionalAttr {
     type Value: ToHtml;
@@ -303,18 +640,252 @@ impl OptionalAttr for bool {
     type Value = Never;
 
     fn attr_value(self) -> Option<Option<Self::Value>> {
-        self.then(|| None)
+        self.then_some(None)
     }
 }
 
+/// A reusable fragment of HTML which can be used inside the [`html`](crate::html!) macro as `Name(prop = value, ...) { children }`, where `Name { prop: value, ... }` is the value of the implementing type.
+///
+/// This is usually implemented using the [`component`](macro@crate::component) attribute.
+pub trait Component {
+    /// Renders the component to the given writer. The `children` closure renders the children passed to the component to the writer it's given.
+    fn render(self, w: &mut dyn HtmlWriter, children: impl FnOnce(&mut dyn HtmlWriter));
+}
+
+/// The contents of a slot of a [`Layout`], or `None` to use the slot's default contents.
+pub type Slot<'a> = Option<Box<dyn FnOnce(&mut dyn HtmlWriter) + 'a>>;
+
+/// A page skeleton with named slots which can be used inside the [`html`](crate::html!) macro as `@extends Name(prop = value, ...) { @slot name { contents } ... }`, where `Name { prop: value, ... }` is the value of the implementing type.
+///
+/// This is usually implemented using the [`layout`](macro@crate::layout) attribute.
+pub trait Layout {
+    /// A struct with a [`Slot`] field for each slot of this layout.
+    type Slots<'a>: Default;
+
+    /// Returns the slots of this layout with their default contents. This is used by the [`html`](crate::html!) macro to name the type of the slots.
+    fn slots<'a>(&self) -> Self::Slots<'a> {
+        Self::Slots::default()
+    }
+
+    /// Renders the layout to the given writer, with the given slot contents.
+    fn render(self, w: &mut dyn HtmlWriter, slots: Self::Slots<'_>);
+}
+
 pub struct Doctype;
 
 impl ToHtml for Doctype {
-    fn to_html(&self) -> RawHtml<String> {
-        RawHtml(format!("<!DOCTYPE html>"))
+    via_write_html!();
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        w.write_raw("<!DOCTYPE html>");
     }
+}
 
-    fn push_html(&self, buf: &mut RawHtml<String>) {
-        buf.0.push_str("<!DOCTYPE html>");
+/// Values which can be interpolated into the body of a `<script>` element inside the [`html`](crate::html!) macro.
+///
+/// Browsers don't decode HTML entities inside `<script>`, so implementors must produce valid JavaScript that can't close the element early. Strings must be wrapped in [`JsString`] to be included as string literals. [`RawHtml`] is included verbatim and must only be used with trusted input.
+pub trait ToScript {
+    fn write_script(&self, w: &mut dyn HtmlWriter);
+}
+
+impl<T: ToScript + ?Sized> ToScript for &T {
+    fn write_script(&self, w: &mut dyn HtmlWriter) {
+        (**self).write_script(w);
+    }
+}
+
+impl<T: ToString> ToScript for RawHtml<T> {
+    fn write_script(&self, w: &mut dyn HtmlWriter) {
+        w.write_raw(&self.0.to_string());
+    }
+}
+
+impl ToScript for Never {
+    fn write_script(&self, _: &mut dyn HtmlWriter) {
+        match *self {}
+    }
+}
+
+impl ToScript for bool {
+    fn write_script(&self, w: &mut dyn HtmlWriter) {
+        w.write_raw(if *self { "true" } else { "false" });
+    }
+}
+
+/// Values which can be interpolated into the body of a `<style>` element inside the [`html`](crate::html!) macro.
+///
+/// Browsers don't decode HTML entities inside `<style>`, so implementors must produce valid CSS that can't close the element early. Strings must be wrapped in [`CssString`] to be included as string literals. [`RawHtml`] is included verbatim and must only be used with trusted input.
+pub trait ToStyle {
+    fn write_style(&self, w: &mut dyn HtmlWriter);
+}
+
+impl<T: ToStyle + ?Sized> ToStyle for &T {
+    fn write_style(&self, w: &mut dyn HtmlWriter) {
+        (**self).write_style(w);
+    }
+}
+
+impl<T: ToString> ToStyle for RawHtml<T> {
+    fn write_style(&self, w: &mut dyn HtmlWriter) {
+        w.write_raw(&self.0.to_string());
+    }
+}
+
+impl ToStyle for Never {
+    fn write_style(&self, _: &mut dyn HtmlWriter) {
+        match *self {}
+    }
+}
+
+macro_rules! impl_to_script_to_style_number {
+    ($($T:ty),* $(,)?) => {
+        $(
+            impl ToScript for $T {
+                fn write_script(&self, w: &mut dyn HtmlWriter) {
+                    write_via_string(w, |buf| write!(buf, "{self}").unwrap());
+                }
+            }
+
+            impl ToStyle for $T {
+                fn write_style(&self, w: &mut dyn HtmlWriter) {
+                    write_via_string(w, |buf| write!(buf, "{self}").unwrap());
+                }
+            }
+        )*
+    };
+}
+
+impl_to_script_to_style_number!(
+    i8,
+    u8,
+    i16,
+    u16,
+    i32,
+    u32,
+    i64,
+    u64,
+    i128,
+    u128,
+    isize,
+    usize,
+    NonZero<i8>,
+    NonZero<u8>,
+    NonZero<i16>,
+    NonZero<u16>,
+    NonZero<i32>,
+    NonZero<u32>,
+    NonZero<i64>,
+    NonZero<u64>,
+    NonZero<i128>,
+    NonZero<u128>,
+    NonZero<isize>,
+    NonZero<usize>,
+);
+
+macro_rules! impl_to_script_to_style_float {
+    ($($T:ty),* $(,)?) => {
+        $(
+            impl ToScript for $T {
+                fn write_script(&self, w: &mut dyn HtmlWriter) {
+                    if self.is_nan() {
+                        w.write_raw("NaN");
+                    } else if self.is_infinite() {
+                        w.write_raw(if self.is_sign_positive() { "Infinity" } else { "-Infinity" });
+                    } else {
+                        write_via_string(w, |buf| write!(buf, "{self}").unwrap());
+                    }
+                }
+            }
+
+            impl ToStyle for $T {
+                fn write_style(&self, w: &mut dyn HtmlWriter) {
+                    write_via_string(w, |buf| write!(buf, "{self}").unwrap());
+                }
+            }
+        )*
+    };
+}
+
+impl_to_script_to_style_float!(f32, f64);
+
+/// Renders a string as a double-quoted JavaScript string literal inside a `<script>` element.
+///
+/// Besides quotes and backslashes, `<`, `>`, `&`, control characters, and U+2028/U+2029 are escaped, so the literal can't close the element early and is also valid JSON.
+pub struct JsString<T: AsRef<str>>(pub T);
+
+impl<T: AsRef<str>> ToScript for JsString<T> {
+    fn write_script(&self, w: &mut dyn HtmlWriter) {
+        write_via_string(w, |buf| {
+            buf.push('"');
+            for c in self.0.as_ref().chars() {
+                match c {
+                    '"' => buf.push_str("\\\""),
+                    '\\' => buf.push_str("\\\\"),
+                    '<' | '>' | '&' | '\u{2028}' | '\u{2029}' => write!(buf, "\\u{:04X}", u32::from(c)).unwrap(),
+                    _ if c.is_control() => write!(buf, "\\u{:04X}", u32::from(c)).unwrap(),
+                    _ => buf.push(c),
+                }
+            }
+            buf.push('"');
+        });
+    }
+}
+
+/// Renders a string as a double-quoted CSS string literal inside a `<style>` element.
+///
+/// All ASCII characters other than letters, digits, and spaces are escaped, so the literal can't close the element early.
+pub struct CssString<T: AsRef<str>>(pub T);
+
+impl<T: AsRef<str>> ToStyle for CssString<T> {
+    fn write_style(&self, w: &mut dyn HtmlWriter) {
+        write_via_string(w, |buf| {
+            buf.push('"');
+            for c in self.0.as_ref().chars() {
+                if c.is_ascii_alphanumeric() || c == ' ' || !c.is_ascii() && !c.is_control() {
+                    buf.push(c);
+                } else {
+                    // the trailing space terminates the escape sequence and is not part of the string
+                    write!(buf, "\\{:X} ", u32::from(c)).unwrap();
+                }
+            }
+            buf.push('"');
+        });
+    }
+}
+
+/// Embeds a value as JSON, e.g. to pass initial state to client-side scripts using `script(type = "application/json", id = "state") : Json(&state);`.
+///
+/// Inside a `<script>` element, `<`, `>`, `&`, U+2028, and U+2029 are escaped as `\uXXXX` (which is only possible inside JSON strings, where the escapes are equivalent), so the JSON can't close the element early. Elsewhere, the same JSON is additionally HTML-escaped.
+///
+/// # Panics
+///
+/// If the value can't be serialized as JSON, e.g. because it's a map with non-string keys.
+#[cfg(feature = "serde_json")]
+pub struct Json<T: Serialize>(pub T);
+
+#[cfg(feature = "serde_json")]
+impl<T: Serialize> ToScript for Json<T> {
+    fn write_script(&self, w: &mut dyn HtmlWriter) {
+        let json = serde_json::to_string(&self.0).expect("failed to serialize value for embedding as JSON");
+        write_via_string(w, |buf| {
+            buf.reserve(json.len());
+            for c in json.chars() {
+                match c {
+                    '<' | '>' | '&' | '\u{2028}' | '\u{2029}' => write!(buf, "\\u{:04X}", u32::from(c)).unwrap(),
+                    _ => buf.push(c),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl<T: Serialize> ToHtml for Json<T> {
+    via_write_html!();
+
+    fn write_html(&self, w: &mut dyn HtmlWriter) {
+        let mut script = String::default();
+        self.write_script(&mut script);
+        escape_into(&script, w);
     }
 }
@@ -1,24 +1,32 @@
 #![deny(rust_2018_idioms, unused, unused_crate_dependencies, unused_import_braces, unused_lifetimes, unused_qualifications, warnings)]
+#![cfg_attr(test, allow(unused_crate_dependencies))] // dev-dependencies are only used by integration tests
 
 use {
     std::{
         borrow::Cow,
         convert::Infallible as Never,
-        fmt::{
-            self,
-            Write as _,
-        },
+        fmt,
+        path::PathBuf,
     },
     rocket::{
+        catcher::{
+            self,
+            Catcher,
+        },
         form::{
             self,
             FromFormField,
         },
         http::{
+            RawStr,
             Status,
+            StatusClass,
+            ext::IntoOwned as _,
             impl_from_uri_param_identity,
             uri::{
                 self,
+                Segments,
+                error::PathError,
                 fmt::{
                     FromUriParam,
                     Path,
@@ -31,57 +39,138 @@ use {
             self,
             FromParam,
             FromRequest,
+            FromSegments,
             Request,
         },
-        response::Responder,
+        response::{
+            Redirect,
+            Responder,
+        },
+        State,
     },
+    url::Url,
 };
-#[cfg(feature = "rocket_csrf")] use {
-    rocket::form::Contextual,
-    rocket_csrf::CsrfToken,
+#[cfg(feature = "csrf")] use rocket::form::Contextual;
+#[cfg(feature = "serde")] use serde::{
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+    de::Error as _,
+    ser::Error as _,
 };
 pub use {
     rocket_util_derive::{
         Error,
+        FromSuffix,
+        component,
         html,
+        html_stream,
+        layout,
+        write_html,
     },
     crate::{
+        head::Head,
         html::{
+            Component,
+            CssString,
             Doctype,
+            Escaped,
+            HashWriter,
+            HtmlWriter,
+            IoWriter,
+            JsString,
+            Layout,
             OptionalAttr,
+            SafeUrl,
+            Slot,
             ToHtml,
+            ToHtmlUrl,
+            ToScript,
+            ToStyle,
         },
         response::{
+            HtmlStream,
+            Negotiate,
             Response,
             WrappedResponder,
         },
     },
 };
 #[doc(hidden)] pub use rocket; // used in proc macro
-#[cfg(feature = "rocket_csrf")] pub use rocket_util_derive::CsrfForm;
+#[cfg(feature = "serde_json")] pub use crate::html::Json;
+#[cfg(feature = "csrf")] pub use {
+    rocket_util_derive::CsrfForm,
+    crate::{
+        csrf::{
+            CsrfFairing,
+            CsrfHeader,
+            CsrfProtected,
+            CsrfToken,
+            CsrfVerificationFailure,
+            unprotected_routes,
+        },
+        html::CsrfMeta,
+    },
+};
 
+#[cfg(feature = "csrf")] mod csrf;
+mod head;
 mod html;
 mod response;
 
-#[cfg(feature = "rocket_csrf")]
+#[cfg(feature = "csrf")]
 pub trait CsrfForm {
-    fn csrf(&self) -> &String;
+    fn csrf(&self) -> &str;
+
+    /// The name of the form field containing the CSRF token. Verification errors are reported for this field.
+    fn csrf_field_name() -> &'static str { "csrf" }
+
+    /// The error message displayed if verification fails, given the value of the request's `Accept-Language` header.
+    ///
+    /// When deriving this trait, this can be overridden using `#[csrf(error_message = path::to::function)]` on the struct.
+    fn csrf_error_message(accept_language: Option<&str>) -> Cow<'static, str> {
+        let _ = accept_language;
+        Cow::Borrowed("Please submit the form again to confirm your identity.")
+    }
 }
 
-#[cfg(feature = "rocket_csrf")]
+#[cfg(feature = "csrf")]
 pub trait ContextualExt {
     fn verify(&mut self, token: &Option<CsrfToken>);
+
+    /// Like [`verify`](ContextualExt::verify), but reads the `Accept-Language` header for [`CsrfForm::csrf_error_message`] from the given request rather than from the token, so it is also available if the token is missing.
+    fn verify_with_request(&mut self, token: &Option<CsrfToken>, request: &Request<'_>);
 }
 
-#[cfg(feature = "rocket_csrf")]
-impl<F: CsrfForm> ContextualExt for Contextual<'_, F> {
-    fn verify(&mut self, token: &Option<CsrfToken>) {
-        if let Some(ref value) = self.value {
-            match token.as_ref().map(|token| token.verify(value.csrf())) {
-                Some(Ok(())) => {}
-                Some(Err(rocket_csrf::VerificationFailure)) | None => self.context.push_error(form::Error::validation("Please submit the form again to confirm your identity.").with_name("csrf")),
+#[cfg(feature = "csrf")]
+/// Returns whether the form was parsed and its token was verified.
+fn verify_contextual<F: CsrfForm>(form: &mut Contextual<'_, F>, token: &Option<CsrfToken>, accept_language: Option<&str>) -> bool {
+    if let Some(ref value) = form.value {
+        match token.as_ref().map(|token| token.verify(value.csrf())) {
+            Some(Ok(())) => true,
+            Some(Err(CsrfVerificationFailure)) | None => {
+                form.context.push_error(form::Error::validation(F::csrf_error_message(accept_language)).with_name(F::csrf_field_name()));
+                false
             }
         }
+    } else {
+        if let Some(token) = token {
+            // the form is already invalid, so there's nothing to protect
+            token.mark_checked();
+        }
+        false
+    }
+}
+
+#[cfg(feature = "csrf")]
+impl<F: CsrfForm> ContextualExt for Contextual<'_, F> {
+    fn verify(&mut self, token: &Option<CsrfToken>) {
+        verify_contextual(self, token, token.as_ref().and_then(|token| token.accept_language()));
+    }
+
+    fn verify_with_request(&mut self, token: &Option<CsrfToken>, request: &Request<'_>) {
+        verify_contextual(self, token, request.headers().get_one("Accept-Language"));
     }
 }
 
@@ -89,12 +178,21 @@ impl<F: CsrfForm> ContextualExt for Contextual<'_, F> {
 #[error(transparent)]
 pub struct Error<E: std::error::Error>(#[from] pub E);
 
+impl<E: std::error::Error> Error<E> {
+    /// Responds with the given status. The error is logged if the status is a server error (5xx).
+    pub fn respond_with_status(self, status: Status, request: &Request<'_>) -> rocket::response::Result<'static> {
+        if status.class() == StatusClass::ServerError {
+            eprintln!("responded with {status} to {} request to {}", request.method(), request.uri());
+            eprintln!("display: {self}");
+            eprintln!("debug: {self:?}");
+        }
+        Err(status)
+    }
+}
+
 impl<'r, E: std::error::Error> Responder<'r, 'static> for Error<E> {
     fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
-        eprintln!("responded with {} to {} request to {}", Status::InternalServerError, request.method(), request.uri());
-        eprintln!("display: {self}");
-        eprintln!("debug: {self:?}");
-        Err(Status::InternalServerError)
+        self.respond_with_status(Status::InternalServerError, request)
     }
 }
 
@@ -113,18 +211,59 @@ impl<'a> FromRequest<'a> for Origin<'a> {
     }
 }
 
+/// Returned by [`Origin::parse_redirect`] if the value is not a safe redirect target.
+#[derive(Debug, thiserror::Error)]
+#[error("not a safe redirect target")]
+pub struct UnsafeRedirect;
+
+impl<'a> Origin<'a> {
+    /// Parses a URL that can safely be used as a redirect target, e.g. from a `?redirect_to=` query parameter.
+    ///
+    /// See [`Origin::is_safe_redirect`] for the rules.
+    pub fn parse_redirect(value: &'a str) -> Result<Self, UnsafeRedirect> {
+        let origin = Self(uri::Origin::try_from(value).map_err(|_| UnsafeRedirect)?);
+        if origin.is_safe_redirect() { Ok(origin) } else { Err(UnsafeRedirect) }
+    }
+
+    /// Checks whether this URL can safely be used as a redirect target, i.e. browsers won't interpret it as pointing to a different host.
+    ///
+    /// This rejects scheme-relative URLs like `//evil.example/`, as well as URLs containing backslashes, whitespace, or control characters, which some browsers normalize or strip such that the URL becomes scheme-relative. The same applies to paths which only become scheme-relative after percent-decoding, like `/%2F/evil.example/`, in case a proxy or the application decodes them.
+    pub fn is_safe_redirect(&self) -> bool {
+        let origin = self.0.to_string();
+        if !origin.starts_with('/') || origin.chars().any(|c| c == '\\' || c.is_whitespace() || c.is_control()) { return false }
+        let path = self.0.path();
+        let path = path.percent_decode_lossy();
+        let mut chars = path.chars().filter(|c| !c.is_whitespace() && !c.is_control());
+        chars.next() == Some('/') && !matches!(chars.next(), Some('/' | '\\'))
+    }
+}
+
 impl<'a> FromFormField<'a> for Origin<'a> {
     fn from_value(field: form::ValueField<'a>) -> form::Result<'a, Self> {
         Ok(Self(uri::Origin::try_from(field.value).map_err(|e| form::Error::validation(e.to_string()))?))
     }
 }
 
+impl<'a> FromParam<'a> for Origin<'a> {
+    type Error = uri::Error<'a>;
+
+    fn from_param(param: &'a str) -> Result<Self, uri::Error<'a>> {
+        uri::Origin::try_from(param).map(Self)
+    }
+}
+
 impl<'a> UriDisplay<Query> for Origin<'a> {
     fn fmt(&self, f: &mut uri::fmt::Formatter<'_, Query>) -> fmt::Result {
         UriDisplay::fmt(&self.0.to_string(), f)
     }
 }
 
+impl<'a> UriDisplay<Path> for Origin<'a> {
+    fn fmt(&self, f: &mut uri::fmt::Formatter<'_, Path>) -> fmt::Result {
+        UriDisplay::fmt(&self.0.to_string(), f)
+    }
+}
+
 impl<'a> FromUriParam<Query, uri::Origin<'a>> for Origin<'a> {
     type Target = Self;
 
@@ -133,7 +272,22 @@ impl<'a> FromUriParam<Query, uri::Origin<'a>> for Origin<'a> {
     }
 }
 
+impl<'a> FromUriParam<Path, uri::Origin<'a>> for Origin<'a> {
+    type Target = Self;
+
+    fn from_uri_param(param: uri::Origin<'a>) -> Self {
+        Self(param)
+    }
+}
+
 impl_from_uri_param_identity!([Query] ('a) Origin<'a>);
+impl_from_uri_param_identity!([Path] ('a) Origin<'a>);
+
+impl<'a> From<uri::Origin<'a>> for Origin<'a> {
+    fn from(origin: uri::Origin<'a>) -> Self {
+        Self(origin)
+    }
+}
 
 impl From<Origin<'_>> for Cow<'_, str> {
     fn from(Origin(origin): Origin<'_>) -> Self {
@@ -147,6 +301,70 @@ impl fmt::Display for Origin<'_> {
     }
 }
 
+#[cfg(feature = "serde")]
+impl Serialize for Origin<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+/// Performs the same validation as the [`FromFormField`] implementation. Always deserializes into an owned value, so this can be used for `Origin<'static>`.
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Origin<'_> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        uri::Origin::parse_owned(String::deserialize(deserializer)?).map(Self).map_err(D::Error::custom)
+    }
+}
+
+/// The absolute URL at which the app is hosted, for generating links in emails, feeds, etc.
+///
+/// Should be added to managed state using [`Rocket::manage`](rocket::Rocket::manage). Can then be used as a request guard.
+#[derive(Debug, Clone)]
+pub struct BaseUrl(pub Url);
+
+impl BaseUrl {
+    /// Returns the absolute URL for the given relative URL, e.g. the output of the [`uri`](rocket::uri) macro.
+    ///
+    /// If the base URL has a path, the relative URL is appended to it, i.e. it is treated as the mount point of the app.
+    pub fn join<'o>(&self, origin: impl Into<Origin<'o>>) -> Url {
+        let Origin(origin) = origin.into();
+        let mut url = self.0.clone();
+        let path = format!("{}{}", url.path().trim_end_matches('/'), origin.path());
+        url.set_path(&path);
+        url.set_query(origin.query().map(|query| query.as_str()));
+        url.set_fragment(None);
+        url
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r BaseUrl {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> request::Outcome<Self, ()> {
+        <&State<BaseUrl>>::from_request(req).await.map(|base_url| base_url.inner())
+    }
+}
+
+/// Redirects to the given URL if it is a [safe redirect target](Origin::is_safe_redirect), or to the fallback URL otherwise.
+pub struct SafeRedirect<'a> {
+    pub target: Option<Origin<'a>>,
+    pub fallback: uri::Origin<'static>,
+}
+
+impl<'r> Responder<'r, 'static> for SafeRedirect<'_> {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let target = match self.target {
+            Some(target) if target.is_safe_redirect() => target.0.into_owned(),
+            Some(_) | None => self.fallback,
+        };
+        Redirect::to(target).respond_to(request)
+    }
+}
+
+/// A path segment consisting of a prefix and an extension, separated by the last `.` in the segment.
+///
+/// When displayed in a URI, the extension is percent-encoded. Formatting fails (so [`uri!`](rocket::uri!) panics) if the extension contains a `.`, since it would not parse back into the same value.
 pub struct Suffix<'a, T>(pub T, pub &'a str);
 
 #[derive(Debug)]
@@ -166,13 +384,33 @@ impl<'a, T: FromParam<'a>> FromParam<'a> for Suffix<'a, T> {
 
 impl<'a, T: UriDisplay<Path>> UriDisplay<Path> for Suffix<'a, T> {
     fn fmt(&self, f: &mut uri::fmt::Formatter<'_, Path>) -> fmt::Result {
+        if self.1.contains('.') { return Err(fmt::Error) }
         self.0.fmt(f)?;
-        write!(f, ".{}", self.1) //TODO ensure URI safety
+        f.write_raw(".")?;
+        UriDisplay::fmt(self.1, f)
     }
 }
 
 impl_from_uri_param_identity!([Path] ('a, T: UriDisplay<Path>) Suffix<'a, T>);
 
+/// Serializes as the string `prefix.ext`, using the [`Display`](fmt::Display) implementation of the prefix. Like the [`UriDisplay`] implementation, this fails if the extension contains a `.`.
+#[cfg(feature = "serde")]
+impl<T: fmt::Display> Serialize for Suffix<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.1.contains('.') { return Err(S::Error::custom("suffix contains a `.`")) }
+        serializer.collect_str(&format_args!("{}.{}", self.0, self.1))
+    }
+}
+
+/// Deserializes from a string using the [`FromParam`] implementation. Since the extension is borrowed, the string must be borrowable from the input, e.g. a JSON string without escape sequences.
+#[cfg(feature = "serde")]
+impl<'de: 'a, 'a, T: FromParam<'a>> Deserialize<'de> for Suffix<'a, T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::from_param(<&str>::deserialize(deserializer)?).map_err(|e| D::Error::custom(format_args!("invalid suffix: {e:?}")))
+    }
+}
+
+/// Like [`Suffix`], but the extension is optional. If it is absent, formatting also fails if the prefix contains a `.`, since that would be parsed as the start of an extension.
 pub struct OptSuffix<'a, T>(pub T, pub Option<&'a str>);
 
 impl<'a, T: FromParam<'a>> FromParam<'a> for OptSuffix<'a, T> {
@@ -190,12 +428,136 @@ impl<'a, T: FromParam<'a>> FromParam<'a> for OptSuffix<'a, T> {
 
 impl<'a, T: UriDisplay<Path>> UriDisplay<Path> for OptSuffix<'a, T> {
     fn fmt(&self, f: &mut uri::fmt::Formatter<'_, Path>) -> fmt::Result {
-        self.0.fmt(f)?;
         if let Some(suffix) = self.1 {
-            write!(f, ".{suffix}")?; //TODO ensure URI safety
+            Suffix(&self.0, suffix).fmt(f)
+        } else {
+            let mut prefix = String::new();
+            fmt::write(&mut prefix, format_args!("{}", &self.0 as &dyn UriDisplay<Path>))?;
+            if RawStr::new(&prefix).percent_decode_lossy().contains('.') { return Err(fmt::Error) }
+            f.write_raw(prefix)
         }
-        Ok(())
     }
 }
 
 impl_from_uri_param_identity!([Path] ('a, T: UriDisplay<Path>) OptSuffix<'a, T>);
+
+/// Serializes as the string `prefix.ext`, or just `prefix` if the extension is absent, using the [`Display`](fmt::Display) implementation of the prefix. Like the [`UriDisplay`] implementation, this fails if the value would not deserialize into the same prefix and extension.
+#[cfg(feature = "serde")]
+impl<T: fmt::Display> Serialize for OptSuffix<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if let Some(suffix) = self.1 {
+            Suffix(&self.0, suffix).serialize(serializer)
+        } else {
+            let prefix = self.0.to_string();
+            if prefix.contains('.') { return Err(S::Error::custom("prefix contains a `.` but there is no suffix")) }
+            serializer.serialize_str(&prefix)
+        }
+    }
+}
+
+/// Deserializes from a string using the [`FromParam`] implementation. Since the extension is borrowed, the string must be borrowable from the input, e.g. a JSON string without escape sequences.
+#[cfg(feature = "serde")]
+impl<'de: 'a, 'a, T: FromParam<'a>> Deserialize<'de> for OptSuffix<'a, T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::from_param(<&str>::deserialize(deserializer)?).map_err(|e| D::Error::custom(format_args!("invalid prefix: {e:?}")))
+    }
+}
+
+/// Types which can be parsed from the extension of a [`TypedSuffix`]. This can be derived for enums.
+pub trait FromSuffix<'a>: Sized {
+    /// Returns `None` if the suffix is not recognized.
+    fn from_suffix(suffix: &'a str) -> Option<Self>;
+
+    /// Splits a file name into a prefix and a suffix, separated by a `.`. Returns `None` if there is no recognized suffix.
+    ///
+    /// The default implementation splits at the last `.` in the file name. Implementations which recognize suffixes consisting of multiple parts, like `tar.gz`, should override this to find the longest recognized suffix.
+    fn split_suffix(param: &'a str) -> Option<(&'a str, Self)> {
+        let (prefix, suffix) = param.rsplit_once('.')?;
+        Some((prefix, Self::from_suffix(suffix)?))
+    }
+}
+
+impl<'a> FromSuffix<'a> for &'a str {
+    fn from_suffix(suffix: &'a str) -> Option<Self> {
+        Some(suffix)
+    }
+}
+
+/// Like [`Suffix`], but the extension is parsed using [`FromSuffix`], e.g. into an enum of supported formats.
+///
+/// `TypedSuffix<PathBuf, E>` can also be used for multi-segment paths like `<path..>`, in which case the suffix is split off the last segment.
+///
+/// If the extension is not recognized, the route forwards and the handler is not called. Rocket forwards failed path parameters with status 422, so register [`unknown_suffix_catcher`] (or use [`is_unknown_suffix`] in a custom 422 catcher) to respond with 404 instead.
+pub struct TypedSuffix<T, E>(pub T, pub E);
+
+#[derive(Debug)]
+pub enum TypedSuffixFromParamError<E> {
+    Prefix(E),
+    Split,
+    /// The extension is not recognized by the [`FromSuffix`] implementation.
+    UnknownSuffix,
+}
+
+impl<'a, T: FromParam<'a>, E: FromSuffix<'a>> FromParam<'a> for TypedSuffix<T, E> {
+    type Error = TypedSuffixFromParamError<T::Error>;
+
+    fn from_param(param: &'a str) -> Result<Self, TypedSuffixFromParamError<T::Error>> {
+        let (prefix, suffix) = split_typed_suffix(param)?;
+        Ok(Self(T::from_param(prefix).map_err(TypedSuffixFromParamError::Prefix)?, suffix))
+    }
+}
+
+fn split_typed_suffix<'a, E: FromSuffix<'a>, Err>(param: &'a str) -> Result<(&'a str, E), TypedSuffixFromParamError<Err>> {
+    E::split_suffix(param).ok_or(if param.contains('.') { TypedSuffixFromParamError::UnknownSuffix } else { TypedSuffixFromParamError::Split })
+}
+
+impl<'r, E: FromSuffix<'r>> FromSegments<'r> for TypedSuffix<PathBuf, E> {
+    type Error = TypedSuffixFromParamError<PathError>;
+
+    fn from_segments(segments: Segments<'r, Path>) -> Result<Self, TypedSuffixFromParamError<PathError>> {
+        let file_name = segments.len().checked_sub(1).and_then(|last| segments.get(last)).ok_or(TypedSuffixFromParamError::Split)?;
+        let (prefix, suffix) = split_typed_suffix(file_name)?;
+        let mut path = PathBuf::from_segments(segments).map_err(TypedSuffixFromParamError::Prefix)?;
+        path.set_file_name(prefix);
+        Ok(Self(path, suffix))
+    }
+}
+
+impl<T: UriDisplay<Path>, E: UriDisplay<Path>> UriDisplay<Path> for TypedSuffix<T, E> {
+    fn fmt(&self, f: &mut uri::fmt::Formatter<'_, Path>) -> fmt::Result {
+        // rendered separately since `T` may consist of multiple segments, e.g. for `PathBuf`
+        let prefix = (&self.0 as &dyn UriDisplay<Path>).to_string();
+        let suffix = (&self.1 as &dyn UriDisplay<Path>).to_string();
+        f.write_raw(format!("{prefix}.{suffix}"))
+    }
+}
+
+impl_from_uri_param_identity!([Path] (T: UriDisplay<Path>, E: UriDisplay<Path>) TypedSuffix<T, E>);
+
+/// Returns `true` if the route tried last for this request has a [`TypedSuffix`] parameter (or any other dynamic segment) whose value has an extension not recognized by `E`.
+///
+/// This is meant to be used in a catcher for status 422, which is what Rocket responds with if a path parameter fails to parse.
+pub fn is_unknown_suffix<E: for<'a> FromSuffix<'a>>(request: &Request<'_>) -> bool {
+    let Some(route) = request.route() else { return false };
+    let segments = request.routed_segments(0..);
+    route.uri.unmounted_origin.path().segments().enumerate()
+        .filter(|(_, route_segment)| route_segment.starts_with('<'))
+        .filter_map(|(idx, route_segment)| if route_segment.ends_with("..>") {
+            segments.len().checked_sub(1).and_then(|last| segments.get(last))
+        } else {
+            segments.get(idx)
+        })
+        .any(|segment| segment.contains('.') && E::split_suffix(segment).is_none())
+}
+
+/// A catcher for status 422 which responds with [`Status::NotFound`] instead if [`is_unknown_suffix`] returns `true`.
+///
+/// The response body is empty. To render custom error pages, write a 422 catcher using [`is_unknown_suffix`] instead.
+pub fn unknown_suffix_catcher<E: for<'a> FromSuffix<'a> + 'static>() -> Catcher {
+    fn handler<'r, E: for<'a> FromSuffix<'a>>(status: Status, request: &'r Request<'_>) -> catcher::BoxFuture<'r> {
+        let status = if is_unknown_suffix::<E>(request) { Status::NotFound } else { status };
+        Box::pin(async move { (status, ()).respond_to(request) })
+    }
+
+    Catcher::new(Status::UnprocessableEntity.code, handler::<E>)
+}
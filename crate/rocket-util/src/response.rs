@@ -1,9 +1,35 @@
-use rocket::{
-    request::Request,
-    response::Responder,
+use {
+    std::{
+        io,
+        mem,
+        pin::Pin,
+        task::{
+            Context,
+            Poll,
+            ready,
+        },
+    },
+    rocket::{
+        http::{
+            ContentType,
+            Header,
+            MediaType,
+            Status,
+        },
+        request::Request,
+        response::Responder,
+        tokio::{
+            io::{
+                AsyncRead,
+                ReadBuf,
+            },
+            sync::mpsc,
+            task,
+        },
+    },
 };
+use crate::HtmlWriter;
 #[cfg(any(feature = "ics", feature = "image", feature = "reqwest", feature = "tiny-skia"))] use crate::Error;
-#[cfg(any(feature = "ics", feature = "image", feature = "tiny-skia"))] use rocket::http::ContentType;
 #[cfg(feature = "ics")] use ics::ICalendar;
 #[cfg(feature = "image")] use {
     std::io::Cursor,
@@ -13,7 +39,6 @@ use rocket::{
     },
 };
 #[cfg(feature = "reqwest")] use {
-    std::io,
     futures::stream::TryStreamExt as _,
     tokio_util::io::StreamReader,
 };
@@ -31,6 +56,154 @@ impl<'r, T: WrappedResponder> Responder<'r, 'static> for Response<T> {
     }
 }
 
+type Renderer<'a> = Box<dyn for<'r> FnOnce(&'r Request<'_>) -> rocket::response::Result<'static> + 'a>;
+
+/// A responder which picks one of several representations of a resource.
+///
+/// The representation is chosen based on the file extension if there is one (e.g. from an [`OptSuffix`](crate::OptSuffix)), and based on the `Accept` header otherwise. If the extension doesn't match any representation, the response is [`Status::NotFound`]. If the `Accept` header doesn't match any representation, the response is [`Status::NotAcceptable`]. If both are absent, the first representation is chosen. If the chosen representation fails to respond, the response has the status it failed with. These error responses have an empty body and are not forwarded to catchers, so that they can include the `Vary` header.
+///
+/// The response has the `Content-Type` of the chosen representation. All responses, including errors, include `Vary: Accept`.
+pub struct Negotiate<'a> {
+    suffix: Option<&'a str>,
+    renderers: Vec<(MediaType, Renderer<'a>)>,
+}
+
+impl<'a> Negotiate<'a> {
+    pub fn new(suffix: Option<&'a str>) -> Self {
+        Self { suffix, renderers: Vec::default() }
+    }
+
+    /// Adds a representation with the given media type. The closure is only called if this representation is chosen.
+    ///
+    /// If multiple representations are equally acceptable, the one added first is chosen.
+    pub fn with<R: for<'r> Responder<'r, 'static>>(mut self, media_type: MediaType, render: impl FnOnce() -> R + 'a) -> Self {
+        self.renderers.push((media_type, Box::new(|request| render().respond_to(request))));
+        self
+    }
+
+    /// Picks the representation to respond with, or the error status to respond with if none is acceptable.
+    fn choose(self, request: &Request<'_>) -> Result<(MediaType, Renderer<'a>), Status> {
+        if let Some(suffix) = self.suffix {
+            let media_type = MediaType::from_extension(suffix).ok_or(Status::NotFound)?;
+            self.renderers.into_iter().find(|(candidate, _)| *candidate == media_type).ok_or(Status::NotFound)
+        } else if let Some(accept) = request.accept() {
+            let mut best = None::<(f32, (MediaType, Renderer<'_>))>;
+            for (candidate, render) in self.renderers {
+                // the most specific matching media range determines the quality
+                let quality = accept.iter()
+                    .filter(|range| {
+                        let range = range.media_type();
+                        (range.top() == "*" || range.top() == candidate.top()) && (range.sub() == "*" || range.sub() == candidate.sub())
+                    })
+                    .max_by_key(|range| range.media_type().specificity())
+                    .map_or(0.0, |range| range.weight_or(1.0));
+                if quality > 0.0 && best.as_ref().is_none_or(|(best_quality, _)| quality > *best_quality) {
+                    best = Some((quality, (candidate, render)));
+                }
+            }
+            best.map(|(_, best)| best).ok_or(Status::NotAcceptable)
+        } else {
+            self.renderers.into_iter().next().ok_or(Status::NotAcceptable)
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Negotiate<'_> {
+    fn respond_to(self, request: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let response = self.choose(request).and_then(|(media_type, render)| {
+            let mut response = render(request)?;
+            response.set_header(ContentType(media_type));
+            Ok(response)
+        });
+        // responding directly rather than forwarding to a catcher, which would drop the `Vary` header
+        let mut response = response.unwrap_or_else(|status| rocket::response::Response::build().status(status).finalize());
+        response.adjoin_header(Header::new("Vary", "Accept"));
+        Ok(response)
+    }
+}
+
+/// The number of chunks an [`HtmlStream`] renders ahead of the client.
+const HTML_STREAM_CAPACITY: usize = 4;
+
+type StreamRenderer = Box<dyn FnOnce(&mut dyn HtmlWriter) + Send>;
+
+/// A responder which renders HTML on a blocking thread while sending it to the client. Created using the [`html_stream`](crate::html_stream!) macro.
+pub struct HtmlStream {
+    render: StreamRenderer,
+}
+
+impl HtmlStream {
+    #[doc(hidden)] // used in proc macro
+    pub fn new(render: impl FnOnce(&mut dyn HtmlWriter) + Send + 'static) -> Self {
+        Self { render: Box::new(render) }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for HtmlStream {
+    fn respond_to(self, _: &'r Request<'_>) -> rocket::response::Result<'static> {
+        let (sender, chunks) = mpsc::channel(HTML_STREAM_CAPACITY);
+        let renderer = task::spawn_blocking(move || {
+            let mut writer = HtmlStreamWriter { buf: String::default(), sender };
+            (self.render)(&mut writer);
+            writer.flush();
+        });
+        rocket::response::Response::build()
+            .header(ContentType::HTML)
+            .streamed_body(HtmlStreamReader { chunks, chunk: String::default(), pos: 0, renderer: Some(renderer) })
+            .ok()
+    }
+}
+
+struct HtmlStreamWriter {
+    buf: String,
+    sender: mpsc::Sender<String>,
+}
+
+impl HtmlWriter for HtmlStreamWriter {
+    fn write_raw(&mut self, html: &str) {
+        self.buf.push_str(html);
+    }
+
+    /// Sends the rendered HTML to the client, blocking while too many chunks are pending. Returns `false` if the client has disconnected.
+    fn flush(&mut self) -> bool {
+        self.buf.is_empty() || self.sender.blocking_send(mem::take(&mut self.buf)).is_ok()
+    }
+}
+
+struct HtmlStreamReader {
+    chunks: mpsc::Receiver<String>,
+    chunk: String,
+    pos: usize,
+    /// Joined once all chunks have been received, so that a panic while rendering ends the response with an error instead of making it look complete.
+    renderer: Option<task::JoinHandle<()>>,
+}
+
+impl AsyncRead for HtmlStreamReader {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        while self.pos == self.chunk.len() {
+            match ready!(self.chunks.poll_recv(cx)) {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                None => {
+                    let Some(renderer) = &mut self.renderer else { return Poll::Ready(Ok(())) };
+                    let result = ready!(Pin::new(renderer).poll(cx));
+                    self.renderer = None;
+                    return Poll::Ready(result.map_err(|e| {
+                        eprintln!("error rendering HTML stream: {e}");
+                        io::Error::other(e)
+                    }))
+                }
+            }
+        }
+        let len = buf.remaining().min(self.chunk.len() - self.pos);
+        buf.put_slice(&self.chunk.as_bytes()[self.pos..self.pos + len]);
+        self.pos += len;
+        Poll::Ready(Ok(()))
+    }
+}
+
 #[cfg(feature = "ics")]
 impl WrappedResponder for ICalendar<'_> {
     fn respond_to(self, request: &Request<'_>) -> rocket::response::Result<'static> {
@@ -66,7 +239,7 @@ fn io_error_from_reqwest(e: reqwest::Error) -> io::Error {
 impl WrappedResponder for reqwest::Response {
     fn respond_to(self, request: &Request<'_>) -> rocket::response::Result<'static> {
         let mut builder = rocket::response::Response::build();
-        builder.status(rocket::http::Status::new(self.status().as_u16()));
+        builder.status(Status::new(self.status().as_u16()));
         for (name, value) in self.headers() {
             match std::str::from_utf8(value.as_bytes()) {
                 Ok(value) => { builder.raw_header_adjoin(name.as_str().to_owned(), value.to_owned()); }
@@ -0,0 +1,413 @@
+#![cfg(feature = "csrf")]
+
+use {
+    std::{
+        borrow::Cow,
+        sync::atomic::{
+            self,
+            AtomicUsize,
+        },
+    },
+    base64::{
+        Engine as _,
+        engine::general_purpose::URL_SAFE_NO_PAD,
+    },
+    hmac::{
+        Hmac,
+        Mac as _,
+    },
+    rocket::{
+        Data,
+        FromForm,
+        Request,
+        State,
+        data::{
+            self,
+            FromData,
+        },
+        form::{
+            Contextual,
+            Form,
+        },
+        http::{
+            ContentType,
+            Cookie,
+            Header,
+            Status,
+        },
+        local::blocking::Client,
+        outcome::try_outcome,
+        response::content::RawHtml,
+    },
+    rocket_util::{
+        ContextualExt as _,
+        CsrfFairing,
+        CsrfForm,
+        CsrfHeader,
+        CsrfMeta,
+        CsrfProtected,
+        CsrfToken,
+        html,
+        unprotected_routes,
+    },
+    sha2::Sha256,
+    url::Url,
+};
+
+#[derive(FromForm, CsrfForm)]
+struct RenamedForm {
+    #[csrf]
+    #[field(name = "token")]
+    authenticity_token: String,
+    value: String,
+}
+
+#[rocket::get("/")]
+fn get(token: CsrfToken) -> RawHtml<String> {
+    html! {
+        form(method = "post") {
+            : token.input::<RenamedForm>();
+            input(name = "value");
+        }
+    }
+}
+
+#[rocket::post("/", data = "<form>")]
+fn post(token: Option<CsrfToken>, mut form: Form<Contextual<'_, RenamedForm>>) -> Status {
+    form.verify(&token);
+    if form.context.errors().next().is_none() && form.value.as_ref().is_some_and(|form| form.value == "x") { Status::Ok } else { Status::BadRequest }
+}
+
+#[rocket::post("/protected", data = "<form>")]
+fn protected(form: CsrfProtected<'_, RenamedForm>) -> Status {
+    match form.value {
+        Some(ref form) if form.value == "x" => Status::Ok,
+        Some(_) => Status::InternalServerError,
+        None => if form.context.errors().any(|error| error.name.as_ref().is_some_and(|name| name == "token")) { Status::BadRequest } else { Status::InternalServerError },
+    }
+}
+
+#[rocket::post("/items/<id>?<page>", data = "<form>")]
+fn protected_with_params(id: u64, page: Option<u64>, form: CsrfProtected<'_, RenamedForm>) -> String {
+    let _ = form;
+    format!("{id} {page:?}")
+}
+
+#[rocket::post("/plain", data = "<form>")]
+fn plain(form: Form<RenamedForm>) -> String {
+    form.into_inner().value
+}
+
+#[rocket::post("/contextual", data = "<form>")]
+fn contextual(token: Option<CsrfToken>, mut form: Form<Contextual<'_, RenamedForm>>) {
+    form.verify(&token);
+}
+
+#[rocket::put("/header")]
+fn header(_header: CsrfHeader) {}
+
+#[rocket::post("/exempt", data = "<form>")]
+fn exempt(form: Form<RenamedForm>) -> String {
+    form.into_inner().value
+}
+
+#[derive(Default)]
+struct Submissions(AtomicUsize);
+
+#[rocket::post("/unprotected", data = "<form>")]
+fn unprotected(submissions: &State<Submissions>, form: Form<RenamedForm>) -> String {
+    submissions.0.fetch_add(1, atomic::Ordering::SeqCst);
+    form.into_inner().value
+}
+
+#[rocket::get("/verify?<token>")]
+fn verify_get(csrf: CsrfToken, token: &str) -> Status {
+    if csrf.verify(token).is_ok() { Status::Ok } else { Status::Forbidden }
+}
+
+#[rocket::get("/meta")]
+fn meta(token: CsrfToken) -> RawHtml<String> {
+    html! {
+        : CsrfMeta(&token);
+        : token.authenticity_token();
+    }
+}
+
+fn client() -> Client {
+    Client::tracked(rocket::build().attach(CsrfFairing::default()).mount("/", rocket::routes![get, post, protected, header, meta])).unwrap()
+}
+
+fn authenticity_token(client: &Client) -> String {
+    let page = client.get("/").dispatch().into_string().unwrap();
+    let (_, rest) = page.split_once(r#"name="token" value=""#).expect("missing token input");
+    rest[..rest.find('"').unwrap()].to_owned()
+}
+
+#[test]
+fn renamed_token_field() {
+    let client = client();
+    let token = authenticity_token(&client);
+    let response = client.post("/").header(ContentType::Form).body(format!("token={token}&value=x")).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn invalid_token() {
+    let client = client();
+    authenticity_token(&client);
+    let response = client.post("/").header(ContentType::Form).body("token=invalid&value=x").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn protected_before_handler() {
+    let client = client();
+    let token = authenticity_token(&client);
+    let response = client.post("/protected").header(ContentType::Form).body(format!("token={token}&value=x")).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = client.post("/protected").header(ContentType::Form).body("token=invalid&value=x").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn report_unprotected_routes() {
+    let rocket = rocket::build()
+        .attach(CsrfFairing::default().enforce().exempt("exempt"))
+        .mount("/", rocket::routes![get, protected, protected_with_params, plain, contextual, header, exempt]);
+    let mut unprotected = unprotected_routes(rocket).await.unwrap().into_iter().map(|route| route.name.unwrap()).collect::<Vec<_>>();
+    unprotected.sort();
+    assert_eq!(unprotected, ["contextual", "plain"]);
+}
+
+/// A plaintext cookie with a key known to the attacker (32 zero bytes), which must not be mistaken for the private cookie set by [`CsrfFairing`].
+fn forged_key_cookie() -> Cookie<'static> {
+    Cookie::new("csrf_key", URL_SAFE_NO_PAD.encode([0; 32]))
+}
+
+#[test]
+fn forged_key_cookie_without_fairing() {
+    let client = Client::tracked(rocket::build().mount("/", rocket::routes![get, protected])).unwrap();
+    let response = client.get("/").cookie(forged_key_cookie()).dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client.post("/protected")
+        .header(ContentType::Form)
+        .cookie(forged_key_cookie())
+        .body(format!("token={}&value=x", forged_token()))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+/// An authenticity token signed with the forged key.
+fn forged_token() -> String {
+    let salt = [0; 16];
+    let mut mac = Hmac::<Sha256>::new_from_slice(&[0; 32]).unwrap();
+    mac.update(&salt);
+    URL_SAFE_NO_PAD.encode([&salt[..], &mac.finalize().into_bytes()].concat())
+}
+
+#[test]
+fn forged_key_cookie_with_fairing() {
+    let client = Client::untracked(rocket::build().attach(CsrfFairing::default()).mount("/", rocket::routes![protected])).unwrap();
+    let response = client.post("/protected")
+        .header(ContentType::Form)
+        .cookie(forged_key_cookie())
+        .body(format!("token={}&value=x", forged_token()))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+}
+
+#[test]
+fn meta_tag() {
+    let client = client();
+    let page = client.get("/meta").dispatch().into_string().unwrap();
+    let (tag, token) = page.split_once('>').expect("missing meta tag");
+    assert_eq!(tag, format!(r#"<meta name="csrf-token" content="{token}""#));
+    let response = client.put("/header").header(Header::new("X-CSRF-Token", token.to_owned())).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn header_token() {
+    let client = client();
+    let token = authenticity_token(&client);
+    let response = client.put("/header").header(Header::new("X-CSRF-Token", token)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let response = client.put("/header").dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client.put("/header").header(Header::new("X-CSRF-Token", "invalid")).dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client.put("/header").header(Header::new("X-CSRF-Token", forged_token())).cookie(forged_key_cookie()).dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+}
+
+fn origin_client() -> Client {
+    Client::tracked(rocket::build()
+        .attach(CsrfFairing::default().site_origin(&Url::parse("https://example.com/app").unwrap()).trust_origin(&Url::parse("https://trusted.example.org").unwrap()))
+        .mount("/", rocket::routes![get, protected, header, verify_get])
+    ).unwrap()
+}
+
+/// Submits the form at `/protected` with a valid authenticity token and the given additional header.
+fn post_with_header(client: &Client, name: &'static str, value: &'static str) -> Status {
+    let token = authenticity_token(client);
+    client.post("/protected").header(ContentType::Form).header(Header::new(name, value)).body(format!("token={token}&value=x")).dispatch().status()
+}
+
+#[test]
+fn same_origin() {
+    let client = origin_client();
+    assert_eq!(post_with_header(&client, "Origin", "https://example.com"), Status::Ok);
+    assert_eq!(post_with_header(&client, "Sec-Fetch-Site", "same-origin"), Status::Ok);
+    assert_eq!(post_with_header(&client, "Sec-Fetch-Site", "none"), Status::Ok);
+}
+
+#[test]
+fn cross_origin() {
+    let client = origin_client();
+    assert_eq!(post_with_header(&client, "Origin", "https://attacker.example"), Status::BadRequest);
+    assert_eq!(post_with_header(&client, "Origin", "http://example.com"), Status::BadRequest);
+    assert_eq!(post_with_header(&client, "Origin", "null"), Status::BadRequest);
+    assert_eq!(post_with_header(&client, "Sec-Fetch-Site", "cross-site"), Status::BadRequest);
+    assert_eq!(post_with_header(&client, "Sec-Fetch-Site", "same-site"), Status::BadRequest);
+    let token = authenticity_token(&client);
+    let response = client.put("/header").header(Header::new("X-CSRF-Token", token)).header(Header::new("Origin", "https://attacker.example")).dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+}
+
+#[test]
+fn trusted_origin() {
+    let client = origin_client();
+    assert_eq!(post_with_header(&client, "Origin", "https://trusted.example.org"), Status::Ok);
+}
+
+#[test]
+fn no_origin_headers() {
+    let client = origin_client();
+    let token = authenticity_token(&client);
+    let response = client.post("/protected").header(ContentType::Form).body(format!("token={token}&value=x")).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+#[test]
+fn origin_check_ignores_get() {
+    let client = origin_client();
+    let token = authenticity_token(&client);
+    let response = client.get(format!("/verify?token={token}")).header(Header::new("Origin", "https://attacker.example")).header(Header::new("Sec-Fetch-Site", "cross-site")).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+}
+
+fn enforcing_client() -> Client {
+    Client::tracked(rocket::build()
+        .attach(CsrfFairing::default().enforce().exempt("exempt"))
+        .manage(Submissions::default())
+        .mount("/", rocket::routes![get, unprotected, exempt])
+    ).unwrap()
+}
+
+fn submissions(client: &Client) -> usize {
+    client.rocket().state::<Submissions>().unwrap().0.load(atomic::Ordering::SeqCst)
+}
+
+#[test]
+fn enforce_rejects_before_handler() {
+    let client = enforcing_client();
+    authenticity_token(&client);
+    let response = client.post("/unprotected").header(ContentType::Form).body("value=x").dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    let response = client.post("/unprotected").header(ContentType::Form).body("token=invalid&value=x").dispatch();
+    assert_eq!(response.status(), Status::Forbidden);
+    assert_eq!(submissions(&client), 0);
+}
+
+#[test]
+fn enforce_accepts_valid_token() {
+    let client = enforcing_client();
+    let token = authenticity_token(&client);
+    let response = client.post("/unprotected").header(ContentType::Form).body(format!("value=x&token={token}")).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_string().as_deref(), Some("x"));
+    let response = client.post("/unprotected").header(ContentType::Form).header(Header::new("X-CSRF-Token", token)).body("token=&value=y").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(submissions(&client), 2);
+}
+
+#[test]
+fn enforce_skips_exempt_routes() {
+    let client = enforcing_client();
+    let response = client.post("/exempt").header(ContentType::Form).body("token=&value=x").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_string().as_deref(), Some("x"));
+}
+
+fn localized_message(accept_language: Option<&str>) -> Cow<'static, str> {
+    if accept_language.is_some_and(|accept_language| accept_language.starts_with("de")) {
+        Cow::Borrowed("Bitte sende das Formular erneut ab.")
+    } else {
+        Cow::Borrowed("Please submit the form again.")
+    }
+}
+
+#[derive(FromForm, CsrfForm)]
+#[csrf(error_message = localized_message)]
+struct LocalizedForm {
+    #[csrf]
+    token: String,
+}
+
+fn token_error(form: &Contextual<'_, LocalizedForm>) -> String {
+    form.context.field_errors("token").map(|error| error.to_string()).collect()
+}
+
+#[rocket::post("/localized", data = "<form>")]
+fn localized(token: Option<CsrfToken>, mut form: Form<Contextual<'_, LocalizedForm>>) -> String {
+    form.verify(&token);
+    token_error(&form)
+}
+
+#[rocket::post("/localized-protected", data = "<form>")]
+fn localized_protected(form: CsrfProtected<'_, LocalizedForm>) -> String {
+    token_error(&form)
+}
+
+/// Verifies the form using [`ContextualExt::verify_with_request`], as a custom data guard would.
+struct VerifiedWithRequest<'r>(Contextual<'r, LocalizedForm>);
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for VerifiedWithRequest<'r> {
+    type Error = rocket::form::Errors<'r>;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let mut form = try_outcome!(Form::<Contextual<'r, LocalizedForm>>::from_data(req, data).await).into_inner();
+        form.verify_with_request(&req.guard::<CsrfToken>().await.succeeded(), req);
+        data::Outcome::Success(Self(form))
+    }
+}
+
+#[rocket::post("/localized-with-request", data = "<form>")]
+fn localized_with_request(form: VerifiedWithRequest<'_>) -> String {
+    token_error(&form.0)
+}
+
+#[test]
+fn localized_error_message() {
+    let client = Client::tracked(rocket::build().attach(CsrfFairing::default()).mount("/", rocket::routes![get, localized, localized_protected, localized_with_request])).unwrap();
+    authenticity_token(&client);
+    for uri in ["/localized", "/localized-protected", "/localized-with-request"] {
+        let response = client.post(uri).header(ContentType::Form).header(Header::new("Accept-Language", "de-CH, en;q=0.5")).body("token=invalid").dispatch();
+        assert_eq!(response.into_string().as_deref(), Some("Bitte sende das Formular erneut ab."), "{uri}");
+        let response = client.post(uri).header(ContentType::Form).header(Header::new("Accept-Language", "en-US")).body("token=invalid").dispatch();
+        assert_eq!(response.into_string().as_deref(), Some("Please submit the form again."), "{uri}");
+        let response = client.post(uri).header(ContentType::Form).body("token=invalid").dispatch();
+        assert_eq!(response.into_string().as_deref(), Some("Please submit the form again."), "{uri}");
+    }
+}
+
+#[test]
+fn localized_error_message_without_token() {
+    // without the fairing, there is no token to read the header from
+    let client = Client::tracked(rocket::build().mount("/", rocket::routes![localized, localized_with_request])).unwrap();
+    let response = client.post("/localized-with-request").header(ContentType::Form).header(Header::new("Accept-Language", "de")).body("token=invalid").dispatch();
+    assert_eq!(response.into_string().as_deref(), Some("Bitte sende das Formular erneut ab."));
+    let response = client.post("/localized").header(ContentType::Form).header(Header::new("Accept-Language", "de")).body("token=invalid").dispatch();
+    assert_eq!(response.into_string().as_deref(), Some("Please submit the form again."));
+}
@@ -0,0 +1,36 @@
+#[test]
+fn error() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/derive/error_pass.rs");
+    cases.compile_fail("tests/ui/derive/error_fail_*.rs");
+}
+
+#[cfg(feature = "csrf")]
+#[test]
+fn csrf_form() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/derive/csrf_form_pass.rs");
+    cases.compile_fail("tests/ui/derive/csrf_form_fail_*.rs");
+}
+
+#[test]
+fn html_stream() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/html_stream/pass.rs");
+    cases.compile_fail("tests/ui/html_stream/flush_in_*.rs");
+}
+
+#[test]
+fn html() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/html/close_*.rs");
+    cases.compile_fail("tests/ui/html/url_*.rs");
+}
+
+// the implementations listed in these errors depend on the enabled features
+#[cfg(not(feature = "serde_json"))]
+#[test]
+fn html_unescaped() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/html/string_in_*.rs");
+}
@@ -0,0 +1,89 @@
+use {
+    std::{
+        env,
+        process::Command,
+    },
+    rocket::{
+        get,
+        http::Status,
+        local::blocking::Client,
+        routes,
+    },
+};
+
+#[derive(Debug, thiserror::Error, rocket_util::Error)]
+#[status(400)]
+enum ApiError {
+    #[status(404)]
+    #[error("no such item")]
+    NotFound,
+    #[error("malformed request")]
+    Malformed,
+    #[status(503)]
+    #[error("database unavailable")]
+    Unavailable,
+}
+
+#[derive(Debug, thiserror::Error, rocket_util::Error)]
+#[error("internal error")]
+struct Internal;
+
+#[get("/not-found")]
+fn not_found() -> Result<(), ApiError> {
+    Err(ApiError::NotFound)
+}
+
+#[get("/malformed")]
+fn malformed() -> Result<(), ApiError> {
+    Err(ApiError::Malformed)
+}
+
+#[get("/unavailable")]
+fn unavailable() -> Result<(), ApiError> {
+    Err(ApiError::Unavailable)
+}
+
+#[get("/internal")]
+fn internal() -> Result<(), Internal> {
+    Err(Internal)
+}
+
+fn client() -> Client {
+    Client::tracked(rocket::build().mount("/", routes![not_found, malformed, unavailable, internal])).expect("valid rocket instance")
+}
+
+#[test]
+fn statuses() {
+    let client = client();
+    assert_eq!(client.get("/not-found").dispatch().status(), Status::NotFound);
+    assert_eq!(client.get("/malformed").dispatch().status(), Status::BadRequest);
+    assert_eq!(client.get("/unavailable").dispatch().status(), Status::ServiceUnavailable);
+    assert_eq!(client.get("/internal").dispatch().status(), Status::InternalServerError);
+}
+
+/// Set when this test binary is run by [`only_server_errors_are_logged`] to produce the log output it checks.
+const CHILD_VAR: &str = "ROCKET_UTIL_TEST_ERROR_LOG_CHILD";
+
+#[test]
+fn only_server_errors_are_logged() {
+    if env::var_os(CHILD_VAR).is_some() {
+        let client = client();
+        for path in ["/not-found", "/malformed", "/unavailable", "/internal"] {
+            client.get(path).dispatch();
+        }
+        return
+    }
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "only_server_errors_are_logged", "--nocapture", "--test-threads=1"])
+        .env(CHILD_VAR, "1")
+        .env("ROCKET_CLI_COLORS", "false")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("responded with 503 Service Unavailable to GET request to /unavailable"), "{stderr}");
+    assert!(stderr.contains("display: database unavailable"), "{stderr}");
+    assert!(stderr.contains("responded with 500 Internal Server Error to GET request to /internal"), "{stderr}");
+    assert!(!stderr.contains("/not-found"), "{stderr}");
+    assert!(!stderr.contains("/malformed"), "{stderr}");
+}
@@ -0,0 +1,78 @@
+use {
+    proptest::prelude::*,
+    rocket::response::content::RawHtml,
+    rocket_util::{
+        Escaped,
+        HtmlWriter,
+        ToHtml,
+        html,
+        write_html,
+    },
+};
+
+mod reference;
+
+/// Strings mixing the bytes which need escaping with multibyte UTF-8 characters, to catch escaping which splits or skips bytes of a character.
+fn special_text() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop_oneof![
+        Just('"'),
+        Just('&'),
+        Just('<'),
+        Just('>'),
+        Just('é'),
+        Just('€'),
+        Just('😀'),
+        Just('\u{3c3c}'), // contains no `<` byte despite its code point
+        any::<char>(),
+    ], 0..64).prop_map(String::from_iter)
+}
+
+/// Writes each piece separately, as [`Escaped`] does with the pieces a `Display` implementation writes.
+struct Pieces<'a>(&'a [String]);
+
+impl std::fmt::Display for Pieces<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|piece| f.write_str(piece))
+    }
+}
+
+fn assert_escapes(text: &str) -> Result<(), TestCaseError> {
+    let expected = reference::escape_html(text);
+    prop_assert_eq!(&text.to_html().0, &expected);
+    prop_assert_eq!(&text.to_owned().to_html().0, &expected);
+    prop_assert_eq!(&Escaped(text).to_html().0, &expected);
+    prop_assert_eq!(&html!(: text;).0, &expected);
+    let mut buf = Vec::default();
+    write_html!(buf, : text;);
+    prop_assert_eq!(String::from_utf8(buf).ok(), Some(expected.clone()));
+    let mut buf = RawHtml(String::default());
+    HtmlWriter::write_raw(&mut buf, "<p>");
+    text.write_html(&mut buf);
+    prop_assert_eq!(buf.0, format!("<p>{expected}"));
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn matches_reference(text in ".*") {
+        assert_escapes(&text)?;
+    }
+
+    #[test]
+    fn matches_reference_around_special_bytes(text in special_text()) {
+        assert_escapes(&text)?;
+    }
+
+    #[test]
+    fn matches_reference_in_pieces(pieces in prop::collection::vec(special_text(), 0..8)) {
+        let expected = reference::escape_html(&pieces.concat());
+        prop_assert_eq!(Escaped(Pieces(&pieces)).to_html().0, expected);
+    }
+}
+
+#[test]
+fn examples() {
+    for text in ["", "plain", "\"", "a & b", "<script>", "x > y", "“quotes” & <ümlauts>", "😀<😀>😀", "&amp;"] {
+        assert_escapes(text).unwrap();
+    }
+}
@@ -0,0 +1,53 @@
+use {
+    rocket::response::content::RawHtml,
+    rocket_util::{
+        Head,
+        html,
+    },
+};
+
+fn head() -> Head {
+    let head = Head::default();
+    head.title("Title");
+    head.stylesheet(rocket::uri!("/style.css"));
+    head
+}
+
+const ENTRIES: &str = r#"<title>Title</title><link rel="stylesheet" href="/style.css">"#;
+
+#[test]
+fn finish() {
+    let page = html! {
+        : rocket_util::Doctype;
+        html {
+            head {
+                meta(charset = "utf-8");
+            }
+            body {
+                header : "<head>";
+            }
+        }
+    };
+    let RawHtml(page) = head().finish(page);
+    assert_eq!(page, format!(r#"<!DOCTYPE html><html><head><meta charset="utf-8">{ENTRIES}</head><body><header>&lt;head&gt;</header></body></html>"#));
+}
+
+#[test]
+fn fallback() {
+    let RawHtml(page) = head().finish(RawHtml(r#"<!DOCTYPE html><html lang="en"><head lang="en"><body><header></header></body></html>"#.to_owned()));
+    assert_eq!(page, format!(r#"<!DOCTYPE html><html lang="en"><head lang="en">{ENTRIES}<body><header></header></body></html>"#));
+    let RawHtml(page) = head().finish(RawHtml("<!doctype html><header></header>".to_owned()));
+    assert_eq!(page, format!("<!doctype html>{ENTRIES}<header></header>"));
+    let RawHtml(page) = head().finish(RawHtml("<p>text</p>".to_owned()));
+    assert_eq!(page, format!("{ENTRIES}<p>text</p>"));
+    let RawHtml(page) = head().finish(RawHtml(String::new()));
+    assert_eq!(page, ENTRIES);
+}
+
+#[test]
+fn clears_entries() {
+    let head = head();
+    let _ = head.finish(RawHtml(String::new()));
+    let RawHtml(page) = head.finish(RawHtml("<head></head>".to_owned()));
+    assert_eq!(page, "<head></head>");
+}
@@ -0,0 +1,67 @@
+use {
+    rocket::{
+        get,
+        http::Status,
+        local::blocking::Client,
+        routes,
+    },
+    rocket_util::{
+        HtmlStream,
+        html_stream,
+    },
+};
+
+#[get("/table")]
+fn table() -> HtmlStream {
+    html_stream! {
+        table {
+            @for row in 0..100 {
+                tr {
+                    td : row;
+                }
+                @flush
+            }
+        }
+    }
+}
+
+fn checked(row: u8) -> u8 {
+    assert!(row < 3, "rendering failed");
+    row
+}
+
+#[get("/panic")]
+fn panic() -> HtmlStream {
+    html_stream! {
+        table {
+            @for row in 0..5 {
+                tr {
+                    td : checked(row);
+                }
+                @flush
+            }
+        }
+    }
+}
+
+fn client() -> Client {
+    Client::tracked(rocket::build().mount("/", routes![table, panic])).expect("valid rocket instance")
+}
+
+#[test]
+fn complete() {
+    let client = client();
+    let response = client.get("/table").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body = response.into_string().expect("complete response body");
+    assert!(body.starts_with("<table><tr><td>0</td></tr>"));
+    assert!(body.ends_with("<tr><td>99</td></tr></table>"));
+}
+
+#[test]
+fn panic_is_not_a_complete_response() {
+    let client = client();
+    let response = client.get("/panic").dispatch();
+    assert_eq!(response.status(), Status::Ok); // headers are sent before rendering
+    assert_eq!(response.into_string(), None);
+}
@@ -0,0 +1,106 @@
+use {
+    std::rc::Rc,
+    rocket::{
+        get,
+        http::{
+            Accept,
+            ContentType,
+            MediaType,
+            Status,
+        },
+        local::blocking::{
+            Client,
+            LocalResponse,
+        },
+        routes,
+    },
+    rocket_util::{
+        Negotiate,
+        OptSuffix,
+    },
+};
+
+#[get("/resource/<file>")]
+fn resource<'a>(file: OptSuffix<'a, &'a str>) -> Negotiate<'a> {
+    Negotiate::new(file.1)
+        .with(MediaType::HTML, || "html")
+        .with(MediaType::JSON, || "json")
+        .with(MediaType::Plain, || "text")
+}
+
+#[get("/missing/<file>")]
+fn missing<'a>(file: OptSuffix<'a, &'a str>) -> Negotiate<'a> {
+    // renderers don't need to be `Send`
+    let text = Rc::new("text");
+    Negotiate::new(file.1)
+        .with(MediaType::HTML, || None::<&str>)
+        .with(MediaType::Plain, move || *text)
+}
+
+fn client() -> Client {
+    Client::tracked(rocket::build().mount("/", routes![resource, missing])).expect("valid rocket instance")
+}
+
+fn negotiate<'c>(client: &'c Client, accept: &str) -> LocalResponse<'c> {
+    client.get("/resource/data").header(accept.parse::<Accept>().expect("valid Accept header")).dispatch()
+}
+
+#[track_caller]
+fn assert_chosen(response: LocalResponse<'_>, content_type: ContentType, body: &str) {
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(content_type));
+    assert_eq!(response.headers().get_one("Vary"), Some("Accept"));
+    assert_eq!(response.into_string().as_deref(), Some(body));
+}
+
+#[track_caller]
+fn assert_error(response: LocalResponse<'_>, status: Status) {
+    assert_eq!(response.status(), status);
+    assert_eq!(response.headers().get_one("Vary"), Some("Accept"));
+}
+
+#[test]
+fn quality_values() {
+    let client = client();
+    assert_chosen(negotiate(&client, "application/json"), ContentType::JSON, "json");
+    assert_chosen(negotiate(&client, "text/html; q=0.5, application/json"), ContentType::JSON, "json");
+    assert_chosen(negotiate(&client, "text/html; q=0.5, application/json; q=0.4"), ContentType::HTML, "html");
+    assert_chosen(negotiate(&client, "text/*; q=0.8, text/plain; q=0.9, application/json; q=0.5"), ContentType::Plain, "text");
+    // the most specific range determines the quality, even if a less specific one has a higher quality
+    assert_chosen(negotiate(&client, "text/*, text/html; q=0, application/json; q=0.5"), ContentType::Plain, "text");
+    assert_chosen(negotiate(&client, "*/*; q=0.1, application/json; q=0.2"), ContentType::JSON, "json");
+}
+
+#[test]
+fn tie_break() {
+    let client = client();
+    assert_chosen(client.get("/resource/data").dispatch(), ContentType::HTML, "html");
+    assert_chosen(negotiate(&client, "*/*"), ContentType::HTML, "html");
+    assert_chosen(negotiate(&client, "application/json, text/html"), ContentType::HTML, "html");
+    assert_chosen(negotiate(&client, "text/plain; q=0.5, application/json; q=0.5"), ContentType::JSON, "json");
+    assert_chosen(negotiate(&client, "text/*"), ContentType::HTML, "html");
+}
+
+#[test]
+fn not_acceptable() {
+    let client = client();
+    assert_error(negotiate(&client, "image/png"), Status::NotAcceptable);
+    assert_error(negotiate(&client, "text/*; q=0, application/json; q=0"), Status::NotAcceptable);
+}
+
+#[test]
+fn suffix() {
+    let client = client();
+    assert_chosen(client.get("/resource/data.json").header(Accept::HTML).dispatch(), ContentType::JSON, "json");
+    assert_chosen(client.get("/resource/data.txt").dispatch(), ContentType::Plain, "text");
+    assert_error(client.get("/resource/data.png").dispatch(), Status::NotFound);
+    assert_error(client.get("/resource/data.unknown").dispatch(), Status::NotFound);
+}
+
+#[test]
+fn renderer_error() {
+    let client = client();
+    assert_error(client.get("/missing/data").dispatch(), Status::NotFound);
+    assert_error(client.get("/missing/data.html").dispatch(), Status::NotFound);
+    assert_chosen(client.get("/missing/data.txt").dispatch(), ContentType::Plain, "text");
+}
@@ -0,0 +1,66 @@
+use {
+    rocket::{
+        get,
+        http::{
+            Status,
+            uri,
+        },
+        local::blocking::Client,
+        routes,
+        uri,
+    },
+    rocket_util::{
+        BaseUrl,
+        Origin,
+    },
+    url::Url,
+};
+
+#[get("/go/<target>")]
+fn go(target: Origin<'_>) -> String {
+    target.to_string()
+}
+
+#[get("/link")]
+fn link(base_url: &BaseUrl) -> String {
+    base_url.join(uri!("/page")).to_string()
+}
+
+#[test]
+fn path_param_round_trip() {
+    let client = Client::tracked(rocket::build().mount("/", routes![go])).unwrap();
+    for target in ["/", "/page", "/a/b?x=1&y=%2F", "/page?next=/other?z"] {
+        let origin = Origin(uri::Origin::parse(target).unwrap());
+        let uri = uri!(go(origin));
+        assert_eq!(uri.path().segments().len(), 2, "{target:?} was not encoded as a single segment");
+        assert!(uri.query().is_none(), "the query of {target:?} leaked into the route's query");
+        let response = client.get(uri.to_string()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().as_deref(), Some(target));
+    }
+}
+
+#[test]
+fn join() {
+    for base in ["https://example.com/app", "https://example.com/app/", "https://example.com/app/#top"] {
+        let base_url = BaseUrl(Url::parse(base).unwrap());
+        assert_eq!(base_url.join(uri!("/page")).as_str(), "https://example.com/app/page", "{base:?}");
+        assert_eq!(base_url.join(uri!("/a/b?x=1&y=2")).as_str(), "https://example.com/app/a/b?x=1&y=2", "{base:?}");
+        assert_eq!(base_url.join(uri!("/")).as_str(), "https://example.com/app/", "{base:?}");
+    }
+    for base in ["https://example.com", "https://example.com/", "https://example.com/?x=1#top"] {
+        let base_url = BaseUrl(Url::parse(base).unwrap());
+        assert_eq!(base_url.join(uri!("/page")).as_str(), "https://example.com/page", "{base:?}");
+        assert_eq!(base_url.join(uri!("/page?y=2")).as_str(), "https://example.com/page?y=2", "{base:?}");
+    }
+}
+
+#[test]
+fn base_url_guard() {
+    let client = Client::tracked(rocket::build().manage(BaseUrl(Url::parse("https://example.com/app/").unwrap())).mount("/", routes![link])).unwrap();
+    let response = client.get("/link").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_string().as_deref(), Some("https://example.com/app/page"));
+    let client = Client::tracked(rocket::build().mount("/", routes![link])).unwrap();
+    assert_eq!(client.get("/link").dispatch().status(), Status::InternalServerError);
+}
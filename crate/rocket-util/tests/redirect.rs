@@ -0,0 +1,100 @@
+use {
+    rocket::{
+        http::{
+            Status,
+            uri,
+        },
+        local::blocking::Client,
+    },
+    rocket_util::{
+        Origin,
+        SafeRedirect,
+    },
+};
+
+/// Payloads which browsers may interpret as pointing to a different host.
+const UNSAFE: &[&str] = &[
+    "//evil.example",
+    "//evil.example/path",
+    "///evil.example",
+    "/\\evil.example",
+    "\\/evil.example",
+    "\\\\evil.example",
+    "/\t/evil.example",
+    "/\n/evil.example",
+    "/\r/evil.example",
+    "/%09/evil.example",
+    "/%0A/evil.example",
+    "/%0d/evil.example",
+    "/%2F%2Fevil.example",
+    "/%2f/evil.example",
+    "/%5Cevil.example",
+    "/%5c%5cevil.example",
+    "https:evil.example",
+    "https://evil.example",
+    "javascript:alert(1)",
+    "evil.example",
+    "",
+    " /evil.example",
+    " //evil.example",
+    "\t//evil.example",
+    "\u{0}/evil.example",
+    "\u{1f}//evil.example",
+    "\u{7f}/evil.example",
+    "/evil\u{0}.example",
+    "/path with spaces",
+];
+
+const SAFE: &[&str] = &[
+    "/",
+    "/path",
+    "/path/to/page?query=value&other=1",
+    "/path?next=//evil.example",
+    "/a%2Fb",
+    "/%E2%9C%93",
+];
+
+#[test]
+fn unsafe_redirects() {
+    for &payload in UNSAFE {
+        assert!(Origin::parse_redirect(payload).is_err(), "accepted unsafe redirect target {payload:?}");
+        if let Ok(origin) = uri::Origin::parse(payload) {
+            assert!(!Origin(origin).is_safe_redirect(), "accepted unsafe redirect target {payload:?}");
+        }
+    }
+}
+
+#[test]
+fn safe_redirects() {
+    for &payload in SAFE {
+        assert!(Origin::parse_redirect(payload).is_ok(), "rejected safe redirect target {payload:?}");
+    }
+}
+
+#[rocket::get("/redirect?<next>")]
+fn redirect(next: Option<&str>) -> SafeRedirect<'_> {
+    SafeRedirect {
+        target: next.and_then(|next| uri::Origin::parse(next).ok()).map(Origin),
+        fallback: rocket::uri!("/fallback"),
+    }
+}
+
+fn location(client: &Client, next: Option<&str>) -> String {
+    let uri = match next {
+        Some(next) => rocket::uri!(redirect(Some(next))),
+        None => rocket::uri!(redirect(None::<&str>)),
+    };
+    let response = client.get(uri.to_string()).dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    response.headers().get_one("Location").unwrap().to_owned()
+}
+
+#[test]
+fn safe_redirect_fallback() {
+    let client = Client::untracked(rocket::build().mount("/", rocket::routes![redirect])).unwrap();
+    assert_eq!(location(&client, Some("/page?x=1")), "/page?x=1");
+    assert_eq!(location(&client, None), "/fallback");
+    for &payload in UNSAFE {
+        assert_eq!(location(&client, Some(payload)), "/fallback", "redirected to unsafe target {payload:?}");
+    }
+}
@@ -0,0 +1,14 @@
+/// The byte-by-byte HTML escaping which was used before escaping in runs found using `memchr`. Kept as an oracle for tests and a baseline for benchmarks.
+pub fn escape_html(s: &str) -> String {
+    let mut escaped = Vec::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'"' => escaped.extend_from_slice(b"&quot;"),
+            b'&' => escaped.extend_from_slice(b"&amp;"),
+            b'<' => escaped.extend_from_slice(b"&lt;"),
+            b'>' => escaped.extend_from_slice(b"&gt;"),
+            _ => escaped.push(b),
+        }
+    }
+    String::from_utf8(escaped).expect("escaping only replaces ASCII bytes")
+}
@@ -0,0 +1,62 @@
+use rocket_util::{
+    CssString,
+    JsString,
+    html,
+};
+
+#[test]
+fn js_string() {
+    let page = html! {
+        script {
+            : "let x = ";
+            : JsString("</script><!--\u{2028}\"\\\n");
+            : ";";
+        }
+    };
+    assert_eq!(page.0, r#"<script>let x = "\u003C/script\u003E\u003C!--\u2028\"\\\u000A";</script>"#);
+    let body = page.0.strip_prefix("<script>").unwrap().strip_suffix("</script>").unwrap();
+    assert!(!body.to_ascii_lowercase().contains("</script") && !body.contains("<!--"));
+}
+
+#[test]
+fn css_string() {
+    let page = html! {
+        style {
+            : "p::after { content: ";
+            : CssString("</style><b>\"x\"");
+            : "; }";
+        }
+    };
+    assert_eq!(page.0, r#"<style>p::after { content: "\3C \2F style\3E \3C b\3E \22 x\22 "; }</style>"#);
+    let body = page.0.strip_prefix("<style>").unwrap().strip_suffix("</style>").unwrap();
+    assert!(!body.to_ascii_lowercase().contains("</style"));
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn json_in_script() {
+    let value = serde_json::json!({
+        "text": "</script>\u{2028}",
+        "html": ["<!--", "&amp;", "\u{2029}"],
+    });
+    for (value, expected) in [
+        (serde_json::json!("</script>\u{2028}"), r#""\u003C/script\u003E\u2028""#),
+        (value, r#"{"html":["\u003C!--","\u0026amp;","\u2029"],"text":"\u003C/script\u003E\u2028"}"#),
+    ] {
+        let page = html! {
+            script(type = "application/json") : rocket_util::Json(&value);
+        };
+        let body = page.0.strip_prefix(r#"<script type="application/json">"#).unwrap().strip_suffix("</script>").unwrap();
+        assert_eq!(body, expected);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(body).unwrap(), value);
+    }
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn json_outside_script() {
+    let page = html! {
+        div(title = rocket_util::Json(&["</script>", "a&b"])) : rocket_util::Json(&"<b>\"x\"</b>");
+    };
+    assert_eq!(page.0, r#"<div title="[&quot;\u003C/script\u003E&quot;,&quot;a\u0026b&quot;]">&quot;\u003Cb\u003E\&quot;x\&quot;\u003C/b\u003E&quot;</div>"#);
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "serde_json")]
+
+use {
+    proptest::prelude::*,
+    rocket::form::{
+        FromFormField as _,
+        ValueField,
+    },
+    rocket_util::{
+        OptSuffix,
+        Origin,
+        Suffix,
+    },
+};
+
+/// Strings which serialize to JSON without escape sequences, so they can be borrowed when deserializing.
+const UNESCAPED: &str = r#"[^"\\\p{Cc}]*"#;
+const UNESCAPED_WITHOUT_DOT: &str = r#"[^."\\\p{Cc}]*"#;
+
+#[test]
+fn serialize() {
+    assert_eq!(serde_json::to_string(&Suffix("archive.tar", "gz")).unwrap(), r#""archive.tar.gz""#);
+    assert_eq!(serde_json::to_string(&Suffix(42, "json")).unwrap(), r#""42.json""#);
+    assert_eq!(serde_json::to_string(&OptSuffix("file", Some("txt"))).unwrap(), r#""file.txt""#);
+    assert_eq!(serde_json::to_string(&OptSuffix("file", None)).unwrap(), r#""file""#);
+    assert!(serde_json::to_string(&Suffix("a", "b.c")).is_err());
+    assert!(serde_json::to_string(&OptSuffix("a.b", None)).is_err());
+}
+
+#[test]
+fn deserialize() {
+    let Suffix(prefix, suffix) = serde_json::from_str::<Suffix<'_, &str>>(r#""archive.tar.gz""#).unwrap();
+    assert_eq!((prefix, suffix), ("archive.tar", "gz"));
+    let Suffix(prefix, suffix) = serde_json::from_str::<Suffix<'_, u8>>(r#""42.json""#).unwrap();
+    assert_eq!((prefix, suffix), (42, "json"));
+    let OptSuffix(prefix, suffix) = serde_json::from_str::<OptSuffix<'_, &str>>(r#""file""#).unwrap();
+    assert_eq!((prefix, suffix), ("file", None));
+    // same validation as `FromParam`
+    assert!(serde_json::from_str::<Suffix<'_, &str>>(r#""file""#).is_err());
+    assert!(serde_json::from_str::<Suffix<'_, u8>>(r#""256.json""#).is_err());
+    assert!(serde_json::from_str::<OptSuffix<'_, u8>>(r#""x""#).is_err());
+    assert!(serde_json::from_str::<Suffix<'_, &str>>(r#"["file", "json"]"#).is_err());
+    // strings with escape sequences can't be borrowed
+    assert!(serde_json::from_str::<Suffix<'_, &str>>(r#""file\u002ejson""#).is_err());
+}
+
+#[test]
+fn origin_round_trip() {
+    for uri in ["/", "/page", "/a/b?x=1&y=%2F", "/%E2%9C%93?next=/other"] {
+        let json = serde_json::to_string(&Origin(rocket::http::uri::Origin::parse(uri).unwrap())).unwrap();
+        assert_eq!(json, format!("{uri:?}"));
+        let origin = serde_json::from_str::<Origin<'static>>(&json).unwrap();
+        drop(json);
+        assert_eq!(origin.to_string(), uri);
+    }
+}
+
+#[test]
+fn invalid_origin() {
+    for uri in ["", "page", "https://example.com/", "/a b", "/page#fragment"] {
+        let form_error = Origin::from_value(ValueField::from_value(uri)).err().map(|errors| errors[0].kind.to_string());
+        let serde_error = serde_json::from_str::<Origin<'static>>(&serde_json::to_string(uri).unwrap()).err();
+        assert!(serde_error.is_some(), "accepted invalid origin {uri:?}");
+        // same validation as `FromFormField`
+        assert_eq!(serde_error.map(|e| e.to_string().split(" at line ").next().unwrap().to_owned()), form_error, "{uri:?}");
+    }
+}
+
+proptest! {
+    #[test]
+    fn suffix_round_trip(prefix in UNESCAPED, suffix in UNESCAPED_WITHOUT_DOT) {
+        let json = serde_json::to_string(&Suffix(&*prefix, &suffix)).unwrap();
+        let Suffix(parsed_prefix, parsed_suffix) = serde_json::from_str::<Suffix<'_, &str>>(&json).unwrap();
+        prop_assert_eq!((parsed_prefix, parsed_suffix), (&*prefix, &*suffix));
+    }
+
+    #[test]
+    fn opt_suffix_round_trip(prefix in UNESCAPED, suffix in proptest::option::of(UNESCAPED_WITHOUT_DOT)) {
+        prop_assume!(suffix.is_some() || !prefix.contains('.'));
+        let json = serde_json::to_string(&OptSuffix(&*prefix, suffix.as_deref())).unwrap();
+        let OptSuffix(parsed_prefix, parsed_suffix) = serde_json::from_str::<OptSuffix<'_, &str>>(&json).unwrap();
+        prop_assert_eq!((parsed_prefix, parsed_suffix), (&*prefix, suffix.as_deref()));
+    }
+}
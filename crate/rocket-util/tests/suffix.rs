@@ -0,0 +1,89 @@
+use {
+    std::fmt,
+    proptest::prelude::*,
+    rocket::{
+        http::uri::{
+            self,
+            fmt::{
+                Path,
+                UriDisplay,
+            },
+        },
+        request::FromParam,
+    },
+    rocket_util::{
+        OptSuffix,
+        Suffix,
+    },
+};
+
+#[rocket::get("/<file>")]
+fn suffix(file: Suffix<'_, &str>) -> String {
+    format!("{}.{}", file.0, file.1)
+}
+
+#[rocket::get("/<file>")]
+fn opt_suffix(file: OptSuffix<'_, &str>) -> String {
+    format!("{}.{:?}", file.0, file.1)
+}
+
+/// Parses the last path segment of the given URI like Rocket does for a path parameter.
+fn last_segment(uri: &uri::Origin<'_>) -> String {
+    uri.path().segments().last().expect("no path segments").to_owned()
+}
+
+fn try_display(value: &dyn UriDisplay<Path>) -> Result<String, fmt::Error> {
+    let mut buf = String::new();
+    fmt::write(&mut buf, format_args!("{value}"))?;
+    Ok(buf)
+}
+
+proptest! {
+    #[test]
+    fn suffix_round_trip(prefix in any::<String>().prop_filter("empty", |prefix| !prefix.is_empty()), ext in "[^.]*") {
+        let uri = rocket::uri!(suffix(Suffix(&*prefix, &ext)));
+        let segment = last_segment(&uri);
+        let parsed = Suffix::<&str>::from_param(&segment).unwrap();
+        prop_assert_eq!(parsed.0, &*prefix);
+        prop_assert_eq!(parsed.1, &*ext);
+    }
+
+    #[test]
+    fn opt_suffix_round_trip(prefix in any::<String>().prop_filter("empty", |prefix| !prefix.is_empty()), ext in proptest::option::of("[^.]*")) {
+        prop_assume!(ext.is_some() || !prefix.contains('.'));
+        let uri = rocket::uri!(opt_suffix(OptSuffix(&*prefix, ext.as_deref())));
+        let segment = last_segment(&uri);
+        let parsed = OptSuffix::<&str>::from_param(&segment).unwrap();
+        prop_assert_eq!(parsed.0, &*prefix);
+        prop_assert_eq!(parsed.1, ext.as_deref());
+    }
+
+    #[test]
+    fn suffix_rejects_dotted_extensions(prefix in any::<String>(), ext in ".*\\..*") {
+        prop_assert!(try_display(&Suffix(&*prefix, &ext)).is_err());
+        prop_assert!(try_display(&OptSuffix(&*prefix, Some(&*ext))).is_err());
+    }
+
+    #[test]
+    fn opt_suffix_rejects_dotted_prefix_without_extension(prefix in ".*\\..*") {
+        prop_assert!(try_display(&OptSuffix(&*prefix, None)).is_err());
+    }
+}
+
+#[test]
+fn examples() {
+    assert!(try_display(&Suffix("a", "b.c")).is_err());
+    assert!(try_display(&OptSuffix("a.b", None)).is_err());
+    assert_eq!(try_display(&Suffix("a.b", "c")).unwrap(), "a.b.c");
+    assert_eq!(try_display(&OptSuffix("a", None)).unwrap(), "a");
+    assert_eq!(try_display(&OptSuffix("a%2Eb", None)).unwrap(), "a%252Eb");
+}
+
+#[test]
+fn routing() {
+    let client = rocket::local::blocking::Client::untracked(rocket::build().mount("/suffix", rocket::routes![suffix]).mount("/opt", rocket::routes![opt_suffix])).unwrap();
+    let uri = rocket::uri!("/suffix", suffix(Suffix("a b.c", "d/e")));
+    assert_eq!(client.get(uri).dispatch().into_string().unwrap(), "a b.c.d/e");
+    let uri = rocket::uri!("/opt", opt_suffix(OptSuffix("a", None::<&str>)));
+    assert_eq!(client.get(uri).dispatch().into_string().unwrap(), "a.None");
+}
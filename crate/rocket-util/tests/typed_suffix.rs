@@ -0,0 +1,89 @@
+use {
+    std::path::PathBuf,
+    rocket::{
+        get,
+        request::FromParam as _,
+        http::Status,
+        local::blocking::Client,
+        routes,
+    },
+    rocket_util::{
+        FromSuffix,
+        Suffix,
+        SuffixFromParamError,
+        TypedSuffix,
+        TypedSuffixFromParamError,
+        unknown_suffix_catcher,
+    },
+};
+
+#[derive(FromSuffix)]
+enum Format {
+    Json,
+    #[suffix("tar.gz")]
+    TarGz,
+}
+
+#[get("/file/<file>")]
+fn file(file: TypedSuffix<&str, Format>) -> String {
+    let TypedSuffix(prefix, format) = file;
+    format!("{prefix} {}", match format {
+        Format::Json => "json",
+        Format::TarGz => "tar.gz",
+    })
+}
+
+#[get("/static/<path..>")]
+fn static_file(path: TypedSuffix<PathBuf, Format>) -> String {
+    path.0.display().to_string()
+}
+
+#[get("/number/<n>")]
+fn number(n: u8) -> String {
+    n.to_string()
+}
+
+fn client() -> Client {
+    Client::tracked(rocket::build()
+        .mount("/", routes![file, static_file, number])
+        .register("/", vec![unknown_suffix_catcher::<Format>()])
+    ).expect("valid rocket instance")
+}
+
+#[test]
+fn known_suffix() {
+    let client = client();
+    let response = client.get("/file/archive.tar.gz").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_string().as_deref(), Some("archive tar.gz"));
+    let response = client.get("/static/a/b.json").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.into_string().as_deref(), Some("a/b"));
+}
+
+#[test]
+fn unknown_suffix() {
+    let client = client();
+    assert_eq!(client.get("/file/data.png").dispatch().status(), Status::NotFound);
+    assert_eq!(client.get("/static/a/b.png").dispatch().status(), Status::NotFound);
+}
+
+#[test]
+fn other_errors() {
+    let client = client();
+    assert_eq!(client.get("/file/data").dispatch().status(), Status::UnprocessableEntity);
+    assert_eq!(client.get("/number/256").dispatch().status(), Status::UnprocessableEntity);
+    assert_eq!(client.get("/missing.png").dispatch().status(), Status::NotFound);
+}
+
+#[test]
+fn errors() {
+    assert!(matches!(TypedSuffix::<&str, Format>::from_param("data.png"), Err(TypedSuffixFromParamError::UnknownSuffix)));
+    assert!(matches!(TypedSuffix::<&str, Format>::from_param("data"), Err(TypedSuffixFromParamError::Split)));
+    assert!(matches!(TypedSuffix::<u8, Format>::from_param("256.json"), Err(TypedSuffixFromParamError::Prefix(_))));
+    // `Suffix` accepts any extension, so its error type has no variant for unknown ones
+    match Suffix::<u8>::from_param("256.json") {
+        Ok(_) | Err(SuffixFromParamError::Split) => panic!("expected an error parsing the prefix"),
+        Err(SuffixFromParamError::Prefix(_)) => {}
+    }
+}
@@ -0,0 +1,6 @@
+#[derive(rocket_util::CsrfForm)]
+struct Form {
+    token: String,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use rocket_util::rocket::FromForm;
+
+#[derive(FromForm, rocket_util::CsrfForm)]
+struct Form {
+    #[csrf]
+    #[field(name = uncased("token"))]
+    token: String,
+}
+
+fn main() {}
@@ -0,0 +1,28 @@
+use rocket_util::CsrfForm;
+
+#[derive(rocket::FromForm, CsrfForm)]
+struct Borrowed<'v> {
+    csrf: &'v str,
+    name: &'v str,
+}
+
+#[derive(rocket::FromForm, CsrfForm)]
+struct Generic<T> {
+    #[csrf]
+    #[field(name = "token")]
+    authenticity_token: String,
+    value: T,
+}
+
+#[derive(CsrfForm)]
+struct ConstGeneric<const N: usize> {
+    csrf: String,
+    values: [u8; N],
+}
+
+fn main() {
+    assert_eq!(Borrowed { csrf: "a", name: "b" }.csrf(), "a");
+    assert_eq!(Generic { authenticity_token: "a".to_owned(), value: 1u8 }.csrf(), "a");
+    assert_eq!(<Generic<u8> as CsrfForm>::csrf_field_name(), "token");
+    assert_eq!(ConstGeneric::<2> { csrf: "a".to_owned(), values: [0; 2] }.csrf(), "a");
+}
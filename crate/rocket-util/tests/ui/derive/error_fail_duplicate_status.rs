@@ -0,0 +1,7 @@
+#[derive(Debug, thiserror::Error, rocket_util::Error)]
+#[status(400)]
+#[status(404)]
+#[error("error")]
+struct E;
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[derive(Debug, thiserror::Error, rocket_util::Error)]
+#[status(600)]
+#[error("too high")]
+struct E;
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[derive(Debug, thiserror::Error, rocket_util::Error)]
+enum E {
+    #[status(99)]
+    #[error("too low")] TooLow,
+}
+
+fn main() {}
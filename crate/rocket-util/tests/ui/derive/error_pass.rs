@@ -0,0 +1,25 @@
+use rocket_util::rocket::response::Responder;
+
+#[derive(Debug, thiserror::Error, rocket_util::Error)]
+enum Generic<T: std::error::Error + 'static> {
+    #[error(transparent)] Inner(#[from] T),
+    #[status(404)]
+    #[error("not found")] NotFound,
+}
+
+#[derive(Debug, thiserror::Error, rocket_util::Error)]
+#[status(400)]
+#[error("invalid value: {0}")]
+struct Borrowed<'v>(&'v str);
+
+#[derive(Debug, thiserror::Error, rocket_util::Error)]
+#[error("expected {N} items")]
+struct ConstGeneric<const N: usize>;
+
+fn assert_responder<'r, R: Responder<'r, 'static>>() {}
+
+fn main() {
+    assert_responder::<Generic<std::io::Error>>();
+    assert_responder::<Borrowed<'static>>();
+    assert_responder::<ConstGeneric<3>>();
+}
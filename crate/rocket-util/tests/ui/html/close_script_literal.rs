@@ -0,0 +1,7 @@
+use rocket_util::html;
+
+fn main() {
+    let _ = html! {
+        script : "let x = '</SCRIPT>';";
+    };
+}
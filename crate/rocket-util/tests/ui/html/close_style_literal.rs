@@ -0,0 +1,7 @@
+use rocket_util::html;
+
+fn main() {
+    let _ = html! {
+        style : "p::after { content: '</style>'; }";
+    };
+}
@@ -0,0 +1,8 @@
+use rocket_util::html;
+
+fn main() {
+    let some_string = String::from("</script><script>alert(1)</script>");
+    let _ = html! {
+        script : some_string;
+    };
+}
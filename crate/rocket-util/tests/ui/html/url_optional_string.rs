@@ -0,0 +1,8 @@
+use rocket_util::html;
+
+fn main() {
+    let url = Some("javascript:alert(1)");
+    let _ = html! {
+        img(src ?= url);
+    };
+}
@@ -0,0 +1,8 @@
+use rocket_util::html;
+
+fn main() {
+    let url = String::from("javascript:alert(1)");
+    let _ = html! {
+        a(href = url) : "link";
+    };
+}
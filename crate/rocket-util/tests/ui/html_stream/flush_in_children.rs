@@ -0,0 +1,24 @@
+use rocket_util::{
+    component,
+    html,
+    html_stream,
+};
+
+#[component]
+fn Wrapper() {
+    html! {
+        div {
+            @children
+        }
+    }
+}
+
+fn main() {
+    let _ = html_stream! {
+        Wrapper {
+            p : "a";
+            @flush
+            p : "b";
+        }
+    };
+}
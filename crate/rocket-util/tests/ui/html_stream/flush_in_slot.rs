@@ -0,0 +1,26 @@
+use rocket_util::{
+    html,
+    html_stream,
+    layout,
+};
+
+#[layout]
+fn Page() {
+    html! {
+        main {
+            @slot content;
+        }
+    }
+}
+
+fn main() {
+    let _ = html_stream! {
+        @extends Page {
+            @slot content {
+                p : "a";
+                @flush
+                p : "b";
+            }
+        }
+    };
+}
@@ -0,0 +1,29 @@
+use rocket_util::{
+    component,
+    html,
+    html_stream,
+};
+
+#[component]
+fn Wrapper() {
+    html! {
+        div {
+            @children
+        }
+    }
+}
+
+fn main() {
+    let _ = html_stream! {
+        ul {
+            @for i in 0..3 {
+                li : i;
+                @flush
+            }
+        }
+        Wrapper {
+            p : "a";
+        }
+        @flush
+    };
+}
@@ -0,0 +1,61 @@
+use {
+    rocket::http::uri,
+    rocket_util::{
+        SafeUrl,
+        html,
+    },
+};
+
+/// URLs which browsers would parse as `javascript:` URLs.
+const UNSAFE: &[&str] = &[
+    "javascript:alert(1)",
+    "JAVASCRIPT:alert(1)",
+    "JavaScript:alert(1)",
+    " javascript:alert(1)",
+    "\u{1}javascript:alert(1)",
+    "\u{0}\u{20}javascript:alert(1)",
+    "java\tscript:alert(1)",
+    "java\nscript:alert(1)",
+    "java\rscript:alert(1)",
+    "\tjava\tscript\t:alert(1)",
+    "data:text/html,<script>alert(1)</script>",
+    "vbscript:msgbox(1)",
+];
+
+fn href(url: &str) -> String {
+    let link = html! {
+        a(href = SafeUrl(url)) : "link";
+    };
+    link.0.strip_prefix(r#"<a href=""#).and_then(|link| link.strip_suffix(r#"">link</a>"#)).unwrap().to_owned()
+}
+
+#[test]
+fn unsafe_urls() {
+    for &url in UNSAFE {
+        assert_eq!(href(url), "about:invalid", "{url:?}");
+    }
+}
+
+#[test]
+fn safe_urls() {
+    assert_eq!(href("https://example.com/?a=1&b=<2>"), "https://example.com/?a=1&amp;b=&lt;2&gt;");
+    assert_eq!(href("HTTP://example.com/"), "HTTP://example.com/");
+    assert_eq!(href("mailto:a@example.com?subject=\"hi\""), "mailto:a@example.com?subject=&quot;hi&quot;");
+    assert_eq!(href("tel:+1-555-0100"), "tel:+1-555-0100");
+    assert_eq!(href("/path?next=javascript:alert(1)"), "/path?next=javascript:alert(1)");
+    assert_eq!(href("page.html#top"), "page.html#top");
+    assert_eq!(href("./javascript:alert(1)"), "./javascript:alert(1)");
+    assert_eq!(href("?x=1&y=2"), "?x=1&amp;y=2");
+    assert_eq!(href(""), "");
+}
+
+#[test]
+fn typed_urls() {
+    let page = html! {
+        a(href = rocket::uri!("/a?b=1&c=2")) : "relative";
+        a(href = url::Url::parse("javascript:alert(1)").unwrap()) : "url";
+        a(href = uri::Absolute::parse("JavaScript:alert(1)").unwrap()) : "absolute";
+        img(src ?= Some(SafeUrl(" javascript:alert(1)")));
+    };
+    assert_eq!(page.0, r#"<a href="/a?b=1&amp;c=2">relative</a><a href="about:invalid">url</a><a href="about:invalid">absolute</a><img src="about:invalid">"#);
+}